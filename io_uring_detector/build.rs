@@ -0,0 +1,7 @@
+fn main() {
+    // Expose the target triple and profile for --build-info
+    for var in ["TARGET", "PROFILE"] {
+        let value = std::env::var(var).unwrap_or_else(|_| String::from("unknown"));
+        println!("cargo:rustc-env=BUILD_{}={}", var, value);
+    }
+}
@@ -0,0 +1,38 @@
+/*
+ * C interface to io_uring_detector, available when the library is built
+ * with the ffi feature (see src/ffi.rs).
+ */
+
+#ifndef IO_URING_DETECTOR_H
+#define IO_URING_DETECTOR_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+struct iou_counts {
+	/* 1 when the detector could create an io_uring instance, else 0 */
+	int32_t supported;
+	/* Processes using io_uring */
+	uint32_t processes;
+	/* Rings held by those processes */
+	uint32_t rings;
+	/* Processes whose fds could not be read for lack of permission */
+	uint32_t permission_denied;
+	/* Processes that exited during the scan */
+	uint32_t vanished;
+};
+
+/* 1 when pid holds an io_uring fd, 0 when it holds none, -1 on error */
+int32_t iou_process_uses_io_uring(uint32_t pid);
+
+/* Scans every process and fills *counts; 0 on success, -1 on error */
+int32_t iou_scan_counts(struct iou_counts *counts);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* IO_URING_DETECTOR_H */
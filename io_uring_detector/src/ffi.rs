@@ -0,0 +1,99 @@
+//! C ABI over the detection functions, for agents written in C
+//!
+//! Enabled by the `ffi` feature. The declarations are in
+//! `include/io_uring_detector.h`; build a library to link against with
+//!
+//! ```text
+//! cargo rustc --release --lib --features ffi --crate-type staticlib \
+//!     --config 'profile.release.panic="unwind"'
+//! ```
+//!
+//! (or `cdylib` for a shared object). No Rust panic crosses the boundary:
+//! each function catches it and returns -1. Catching needs the unwinding
+//! panic strategy, which the release profile otherwise turns off.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::report::run;
+use crate::ring::process_uses_io_uring;
+use crate::scan::proc_root;
+
+/// Totals of one scan, filled in by [`iou_scan_counts`]
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct IouCounts {
+    /// 1 when the detector could create an io_uring instance, else 0
+    pub supported: i32,
+    /// Processes using io_uring
+    pub processes: u32,
+    /// Rings held by those processes
+    pub rings: u32,
+    /// Processes whose fds could not be read for lack of permission
+    pub permission_denied: u32,
+    /// Processes that exited during the scan
+    pub vanished: u32,
+}
+
+/// Returns 1 when PID holds an io_uring fd, 0 when it holds none, and -1
+/// when its fds could not be read
+#[no_mangle]
+pub extern "C" fn iou_process_uses_io_uring(pid: u32) -> i32 {
+    let result = catch_unwind(|| process_uses_io_uring(&proc_root(None), pid));
+    match result {
+        Ok(Ok(usage)) => i32::from(usage.uses_io_uring),
+        Ok(Err(_)) | Err(_) => -1,
+    }
+}
+
+/// Probes io_uring support, scans every process and writes the totals to
+/// `counts`
+///
+/// Returns 0 on success and -1 when `counts` is null or the probe or scan
+/// failed, in which case `counts` is left untouched.
+///
+/// # Safety
+///
+/// `counts` must be null or point to an `IouCounts` the caller may write.
+#[no_mangle]
+pub unsafe extern "C" fn iou_scan_counts(counts: *mut IouCounts) -> i32 {
+    if counts.is_null() {
+        return -1;
+    }
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        run().map(|report| IouCounts {
+            supported: i32::from(report.supported),
+            processes: saturating_u32(report.processes.len()),
+            rings: saturating_u32(report.processes.iter().map(|detection| detection.rings.len()).sum()),
+            permission_denied: saturating_u32(report.permission_denied_count),
+            vanished: saturating_u32(report.vanished_count),
+        })
+    }));
+    match result {
+        Ok(Ok(totals)) => {
+            unsafe { counts.write(totals) };
+            0
+        }
+        Ok(Err(_)) | Err(_) => -1,
+    }
+}
+
+/// Narrows a count for the C struct, capping it rather than wrapping
+fn saturating_u32(count: usize) -> u32 {
+    u32::try_from(count).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_null_counts() {
+        assert_eq!(unsafe { iou_scan_counts(std::ptr::null_mut()) }, -1);
+    }
+
+    #[test]
+    fn missing_process_is_an_error() {
+        // PIDs never exceed 2^22, so this one cannot exist
+        assert_eq!(iou_process_uses_io_uring(u32::MAX), -1);
+    }
+}
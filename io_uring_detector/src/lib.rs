@@ -0,0 +1,26 @@
+//! Detection of io_uring support and usage on Linux
+//!
+//! Probes whether the running kernel supports io_uring, gathers host
+//! information that affects it, and scans /proc for processes holding
+//! io_uring instances. The `io_uring_detector` binary is a command line
+//! front end over this crate.
+
+#[cfg(all(feature = "bpf", target_os = "linux"))]
+pub mod bpf;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod net;
+pub mod probe;
+pub mod process;
+pub mod report;
+pub mod ring;
+pub mod scan;
+pub mod system;
+
+pub use net::Connection;
+pub use probe::{detect_io_uring_support, IoUringParams, UringStatus};
+pub use process::{MemoryInfo, ProcessInfo};
+pub use report::{run, run_with, run_without_scan, Report, ScannedProcess, SCHEMA_VERSION};
+pub use ring::{process_uses_io_uring, ProcessUsage};
+pub use scan::{scan_processes, Detection, ScanOptions, ScanResult, ScanStatus};
+pub use system::{system_info, SystemInfo};
@@ -1,375 +1,2923 @@
-use std::fs::{read_dir, read_link, read_to_string};
+use std::fs::{create_dir_all, read_link, read_to_string, remove_dir_all, DirBuilder, File};
 use std::io;
-use std::path::PathBuf;
 use std::os::fd::RawFd;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::error::ErrorKind;
+use clap::{ArgAction, CommandFactory, Parser, ValueEnum};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 
 #[cfg(target_os = "linux")]
-use libc::{c_uint, syscall, SYS_io_uring_setup, uname, utsname};
+use libc::SYS_io_uring_setup;
+
+use io_uring_detector::probe::{
+    decode_features, detect_restrictions_support, detect_supported_ops, expected_io_uring_setup_nr,
+    find_io_uring_setup_symbol, IoUringParams, UringStatus,
+};
+use io_uring_detector::process::{
+    fd_target_kind, format_rfc3339, get_process_name, get_process_state, username_for_uid, ProcessInfo,
+};
+use io_uring_detector::report::{run_with, run_without_scan, Report, ScannedProcess};
+use io_uring_detector::ring::ring_fds_with_inodes;
+use io_uring_detector::scan::{
+    find_sqpoll_threads, group_rings_by_inode, holders_unrelated, is_high_risk, ring_inodes_unique,
+    proc_root, runs_fileless, scan_processes, total_memory, Detection, DetectionMethod, IoUringThread, ScanOptions,
+    ScanResult, ScanStatus, FAST_MIN_FDS,
+};
+use io_uring_detector::system::{
+    has_cap_sys_admin, kernel_meets, parse_kernel_version, system_info, KernelConfig, SupportReason,
+    SystemInfo, IO_URING_CONFIG_OPTIONS, MIN_KERNEL_VERSION,
+};
+
+/// Prints the syscall number the binary was built with and checks it
+/// against the running kernel, without creating an io_uring instance
+fn print_dry_probe(sys_info: &SystemInfo) {
+    println!("Syscall probe diagnostics:");
+    println!("  Compiled for: {}", std::env::consts::ARCH);
+    println!("  Kernel architecture: {}", sys_info.architecture);
+
+    #[cfg(target_os = "linux")]
+    {
+        // c_long is 32 bits wide on 32-bit targets
+        #[allow(clippy::unnecessary_cast)]
+        let compiled = SYS_io_uring_setup as i64;
+        println!("  SYS_io_uring_setup (compiled): {}", compiled);
+
+        match expected_io_uring_setup_nr(&sys_info.architecture) {
+            Some(expected) if expected == compiled => {
+                println!("  SYS_io_uring_setup (expected): {} (match)", expected);
+            }
+            Some(expected) => {
+                println!("  SYS_io_uring_setup (expected): {} (MISMATCH)", expected);
+                println!("  Warning: this binary may have been built for a different ABI");
+            }
+            None => {
+                println!("  SYS_io_uring_setup (expected): unknown for this architecture");
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        println!("  SYS_io_uring_setup: not available on this platform");
+    }
+
+    match find_io_uring_setup_symbol() {
+        Ok(Some(symbol)) => println!("  Kernel symbol: {} (present in /proc/kallsyms)", symbol),
+        Ok(None) => println!("  Kernel symbol: io_uring_setup not found in /proc/kallsyms"),
+        Err(e) => println!("  Kernel symbol: /proc/kallsyms unavailable ({})", e),
+    }
+}
+
+fn print_io_uring_features(params: &IoUringParams) {
+    println!("\nReported io_uring feature flags:");
+    let (names, unknown) = decode_features(params.features);
+
+    for name in &names {
+        println!("  - {}", name);
+    }
+
+    if names.is_empty() && unknown == 0 {
+        println!("  (no features reported)");
+    }
+
+    for bit in (0..u32::BITS).filter(|bit| unknown & (1 << bit) != 0) {
+        println!("  - unknown feature (bit {})", bit);
+    }
+    if unknown != 0 {
+        println!("  Note: the kernel reports features this tool does not know about; its feature table may be out of date.");
+    }
+}
+
+/// Prints whether rings can be sandboxed with IORING_REGISTER_RESTRICTIONS,
+/// from a fresh disabled probe ring
+fn print_restrictions_support() {
+    match detect_restrictions_support() {
+        Ok(Some(true)) => println!("\nRing restrictions: supported (IORING_REGISTER_RESTRICTIONS can lock rings down)"),
+        Ok(Some(false)) => println!("\nRing restrictions: not supported (added in 5.10)"),
+        Ok(None) => println!("\nRing restrictions: unknown (io_uring_setup is unavailable)"),
+        Err(e) => println!("\nRing restrictions: unknown ({})", e),
+    }
+}
+
+/// Prints which submission opcodes the kernel supports, from a fresh probe ring
+fn print_supported_ops() {
+    println!("\nSupported io_uring operations:");
+    match detect_supported_ops() {
+        Ok(Some(ops)) => {
+            for (op, name, supported) in &ops {
+                let status = if *supported { "supported" } else { "not supported" };
+                println!("  {:>3} {:<28} {}", op, name, status);
+            }
+            let count = ops.iter().filter(|(_, _, supported)| *supported).count();
+            println!("  {} of {} opcodes supported", count, ops.len());
+        }
+        Ok(None) => println!("  (io_uring_setup is unavailable)"),
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+            println!("  (unavailable: this kernel predates IORING_REGISTER_PROBE, added in 5.6)");
+        }
+        Err(e) => println!("  (unavailable: {})", e),
+    }
+}
+
+/// Prints every field of the io_uring_setup result, offsets included
+fn print_io_uring_params(params: &IoUringParams) {
+    let sq = &params.sq_off;
+    let cq = &params.cq_off;
+    let rows: &[(&str, String)] = &[
+        ("sq_entries", params.sq_entries.to_string()),
+        ("cq_entries", params.cq_entries.to_string()),
+        ("flags", format!("{:#x}", params.flags)),
+        ("sq_thread_cpu", params.sq_thread_cpu.to_string()),
+        ("sq_thread_idle", params.sq_thread_idle.to_string()),
+        ("features", format!("{:#x}", params.features)),
+        ("wq_fd", params.wq_fd.to_string()),
+        ("sq_off.head", sq.head.to_string()),
+        ("sq_off.tail", sq.tail.to_string()),
+        ("sq_off.ring_mask", sq.ring_mask.to_string()),
+        ("sq_off.ring_entries", sq.ring_entries.to_string()),
+        ("sq_off.flags", sq.flags.to_string()),
+        ("sq_off.dropped", sq.dropped.to_string()),
+        ("sq_off.array", sq.array.to_string()),
+        ("sq_off.resv1", sq.resv1.to_string()),
+        ("sq_off.resv2", format!("{:#x}", sq.resv2)),
+        ("cq_off.head", cq.head.to_string()),
+        ("cq_off.tail", cq.tail.to_string()),
+        ("cq_off.ring_mask", cq.ring_mask.to_string()),
+        ("cq_off.ring_entries", cq.ring_entries.to_string()),
+        ("cq_off.overflow", cq.overflow.to_string()),
+        ("cq_off.cqes", cq.cqes.to_string()),
+        ("cq_off.flags", cq.flags.to_string()),
+        ("cq_off.resv1", cq.resv1.to_string()),
+        ("cq_off.resv2", format!("{:#x}", cq.resv2)),
+    ];
+
+    println!("\nio_uring_setup parameters:");
+    for (name, value) in rows {
+        println!("  {:<20} {}", name, value);
+    }
+}
+
+/// Describes a process state character as shown in /proc/<pid>/stat
+fn describe_process_state(state: char) -> &'static str {
+    match state {
+        'R' => "running",
+        'S' => "sleeping",
+        'D' => "uninterruptible sleep",
+        'T' => "stopped",
+        't' => "tracing stop",
+        'Z' => "zombie",
+        'X' => "dead",
+        'I' => "idle",
+        'P' => "parked",
+        _ => "unknown",
+    }
+}
+
+/// Quotes a command line argument so it can be pasted back into a POSIX shell
+fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Whether the text report uses ANSI colors, decided once in main
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// ANSI color codes used by the text report
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+
+/// Wraps text in an ANSI color when colored output is enabled
+fn paint(text: &str, color: &str) -> String {
+    if COLOR.load(Ordering::Relaxed) {
+        format!("\x1b[{}m{}\x1b[0m", color, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Whether to color the output: only on a terminal, and not with
+/// --no-color or a non-empty NO_COLOR (https://no-color.org)
+fn use_color(options: &Options) -> bool {
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !options.no_color && !no_color_env && unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1
+}
+
+/// Prints the report for a single io_uring process
+///
+/// `detections` is the whole scan, used to find which processes hold the
+/// fds of rings this one only has mapped. Before kernel 5.12 every ring
+/// shares one anonymous inode, so the holders are not listed there.
+fn print_detection(proc_root: &Path, sys_info: &SystemInfo, detection: &Detection, detections: &[Detection]) {
+    let info = &detection.info;
+
+    println!("\n{}", paint("Process using io_uring:", RED));
+    println!("  PID: {}", paint(&detection.pid.to_string(), RED));
+    if info.name == info.comm {
+        println!("  Name: {}", info.name);
+    } else {
+        println!("  Name: {} (comm: {})", info.name, info.comm);
+    }
+
+    match (&info.username, info.uid) {
+        (Some(username), Some(uid)) => println!("  Owner: {} ({})", username, uid),
+        (None, Some(uid)) => println!("  Owner: {}", uid),
+        _ => {}
+    }
+
+    if let Some(path) = &info.exe_path {
+        println!("  Executable: {}", path.display());
+    } else {
+        println!("  Executable: <unavailable>");
+    }
+
+    if let Some(cgroup) = &info.cgroup {
+        println!("  Cgroup: {}", cgroup);
+    }
+
+    if let Some(container_id) = &info.container_id {
+        println!("  Container: {}", container_id);
+    }
+
+    if let Some(start_time) = info.start_time {
+        println!("  Started: {}", format_rfc3339(start_time));
+    }
+
+    if info.mount_ns_differs {
+        println!("  Note: process is in a different mount namespace; its executable path");
+        println!("        refers to that namespace and may not exist from the detector's view");
+    }
+
+    if info.root_differs {
+        let root = read_link(proc_root.join(format!("{}/root", detection.pid)))
+            .map(|root| root.display().to_string())
+            .unwrap_or_else(|_| String::from("<unavailable>"));
+        println!("  Note: process has a different root directory ({}); paths it uses", root);
+        println!(
+            "        that the detector cannot reach are resolved through {}/{}/root",
+            proc_root.display(),
+            detection.pid
+        );
+    }
+
+    if let Some(cmdline) = &info.cmdline {
+        let quoted: Vec<String> = cmdline.iter().map(|arg| shell_quote(arg)).collect();
+        println!("  Command line: {}", quoted.join(" "));
+    }
+
+    println!("  State: {} ({})", info.state, describe_process_state(info.state));
+    if info.state == 'D' {
+        println!(
+            "  {}",
+            paint(
+                "Warning: process is in uninterruptible sleep while holding an io_uring ring (possible I/O hang)",
+                YELLOW
+            )
+        );
+    }
+
+    if info.exe_is_memory_backed {
+        println!("  Status: Running in memory (executable is a memfd or deleted from disk)");
+    }
+
+    if let Some(memory) = &info.memory_status {
+        if let Some(vm) = memory.virtual_memory {
+            println!("  Virtual Memory: {} kB", vm);
+        }
+        if let Some(rss) = memory.resident_memory {
+            println!("  Resident Memory: {} kB", rss);
+        }
+    }
+
+    let fds: Vec<String> = detection.rings.iter().map(|ring| ring.fd.to_string()).collect();
+    if fds.is_empty() {
+        println!("  io_uring FDs: none");
+    } else {
+        println!("  io_uring FDs: {}", fds.join(", "));
+    }
+    print_other_fds(info);
+    print_connections(info);
+
+    if info.io_uring_threads.is_empty() {
+        println!("  Threads: {}", info.threads.len());
+    } else {
+        let names: Vec<String> = info
+            .io_uring_threads
+            .iter()
+            .map(|(tid, name)| format!("{} (TID {})", name, tid))
+            .collect();
+        println!("  Threads: {} (io_uring: {})", info.threads.len(), names.join(", "));
+    }
+
+    let method = match detection.detected_via {
+        DetectionMethod::Fd => "ring fd (not mapped)",
+        DetectionMethod::Mapping => "ring mapping only",
+        DetectionMethod::Both => "ring fd and mapping",
+    };
+    println!("  Detected via: {}", method);
+    let inodes_unique = ring_inodes_unique(sys_info);
+    for inode in &detection.mapped_ring_inodes {
+        if !inodes_unique {
+            println!("  Mapped ring inode {} without an fd (holders unknown: rings share an inode before 5.12)", inode);
+            continue;
+        }
+        let holders: Vec<String> = detections
+            .iter()
+            .filter(|other| other.pid != detection.pid)
+            .filter(|other| other.rings.iter().any(|ring| ring.inode == Some(*inode)))
+            .map(|other| format!("PID {} ({})", other.pid, other.info.name))
+            .collect();
+        if holders.is_empty() {
+            println!("  Mapped ring inode {} without an fd", inode);
+        } else {
+            println!("  Mapped ring inode {} without an fd; held by {}", inode, holders.join(", "));
+        }
+    }
+
+    for ring in &detection.rings {
+        println!("  Ring fd {}:", ring.fd);
+        if let Some(entries) = ring.sq_entries {
+            let kind = if ring.is_probe_ring() {
+                "probe, likely benign"
+            } else {
+                "workload"
+            };
+            println!("    SQ entries: {} ({})", entries, kind);
+        }
+        if let Some(entries) = ring.cq_entries {
+            // The kernel makes the CQ twice the SQ unless IORING_SETUP_CQSIZE
+            // asked for another size
+            if ring.sq_entries.is_some_and(|sq| sq.checked_mul(2) != Some(entries)) {
+                println!("    CQ entries: {} (custom size, IORING_SETUP_CQSIZE)", entries);
+            } else {
+                println!("    CQ entries: {}", entries);
+            }
+        }
+        if ring.uses_sqpoll() {
+            let cpu = match ring.sq_thread_cpu {
+                Some(cpu) if cpu >= 0 => cpu.to_string(),
+                _ => String::from("any"),
+            };
+            let idle = match ring.sq_thread_idle {
+                Some(idle) => format!("{} ms", idle),
+                None => String::from("not exposed by this kernel"),
+            };
+            println!(
+                "    SQPOLL: yes (thread {}, CPU {}, idle timeout {})",
+                ring.sq_thread.unwrap_or(-1),
+                cpu,
+                idle
+            );
+            if let Some(sched) = ring.sq_thread_sched {
+                if sched.is_realtime() {
+                    println!("    SQPOLL scheduling: {} (rt priority {})", sched.policy_name(), sched.rt_priority);
+                } else {
+                    println!("    SQPOLL scheduling: {} (nice {})", sched.policy_name(), sched.nice);
+                }
+            }
+        } else {
+            println!("    SQPOLL: no");
+        }
+        if !ring.registered_files.is_empty() {
+            println!("    Registered files: {}", ring.registered_files.len());
+        }
+        if let Some(count) = ring.registered_buffers.filter(|&count| count > 0) {
+            match ring.registered_buffer_bytes {
+                Some(bytes) => println!("    Registered buffers: {} ({} kB pinned)", count, bytes.div_ceil(1024)),
+                None => println!("    Registered buffers: {}", count),
+            }
+        }
+        if let Some(workload) = &ring.inferred_workload {
+            println!("    Inferred workload: {}", workload);
+        }
+        if let Some(overflow) = ring.cq_overflow.filter(|&n| n > 0) {
+            println!("    Warning: {} completion(s) on the CQ overflow list; the ring is overloaded", overflow);
+        }
+        for device in &ring.backing_devices {
+            println!("    Warning: direct device I/O through registered {}", device.display());
+        }
+        match &ring.napi {
+            Some(napi) if napi.enabled => {
+                let mut details = Vec::new();
+                if let Some(timeout) = napi.busy_poll_timeout_us {
+                    details.push(format!("timeout {} us", timeout));
+                }
+                if napi.prefer_busy_poll == Some(true) {
+                    details.push(String::from("prefer busy poll"));
+                }
+                if details.is_empty() {
+                    println!("    NAPI busy poll: enabled");
+                } else {
+                    println!("    NAPI busy poll: enabled ({})", details.join(", "));
+                }
+            }
+            Some(_) => println!("    NAPI busy poll: disabled"),
+            None => println!("    NAPI busy poll: unavailable on this kernel"),
+        }
+    }
+}
+
+/// Prints how many of a process's other fds are sockets, files, pipes and
+/// anonymous inodes, to tell network-heavy io_uring use from disk I/O
+fn print_other_fds(info: &ProcessInfo) {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (_, target) in &info.open_fds {
+        if target != "anon_inode:[io_uring]" {
+            *counts.entry(fd_target_kind(target)).or_default() += 1;
+        }
+    }
+    let count = |kind| counts.get(kind).copied().unwrap_or(0);
+
+    let mut parts = vec![plural(count("socket"), "socket", "sockets"), plural(count("file"), "file", "files")];
+    for (kind, singular, many) in [
+        ("pipe", "pipe", "pipes"),
+        ("anon_inode", "anonymous inode", "anonymous inodes"),
+        ("other", "other", "other"),
+    ] {
+        if count(kind) > 0 {
+            parts.push(plural(count(kind), singular, many));
+        }
+    }
+    println!("  Other FDs: {}", parts.join(", "));
+}
+
+/// Lists the process's TCP and UDP sockets, with the remote end for those
+/// that have one
+fn print_connections(info: &ProcessInfo) {
+    if info.connections.is_empty() {
+        return;
+    }
+    println!("  Connections:");
+    for connection in &info.connections {
+        let state = connection.state.map(|state| format!(" ({})", state)).unwrap_or_default();
+        if connection.has_remote() {
+            println!("    {} {} -> {}{}", connection.protocol, connection.local, connection.remote, state);
+        } else {
+            println!("    {} {}{}", connection.protocol, connection.local, state);
+        }
+    }
+}
+
+/// Prints every scanned PID with whether it uses io_uring
+fn print_process_inventory(processes: &[ScannedProcess]) {
+    println!("\nAll scanned processes:");
+    println!("  {:<8} {:<16} USES_IO_URING", "PID", "NAME");
+    for process in processes {
+        let name = process.name.as_deref().unwrap_or("<unknown>");
+        let uses = match process.status {
+            ScanStatus::UsesIoUring => "yes",
+            ScanStatus::Clean => "no",
+            ScanStatus::Unreadable => "unknown (fd directory unreadable)",
+            ScanStatus::PermissionDenied => "unknown (permission denied)",
+            ScanStatus::Exiting => "unknown (exiting)",
+            ScanStatus::Skipped => "unknown (skipped by --fast)",
+            ScanStatus::Vanished => "unknown (exited during the scan)",
+        };
+        println!("  {:<8} {:<16} {}", process.pid, name, uses);
+    }
+}
+
+/// Orders detections by the requested key, ties broken by PID
+fn sort_detections(detections: &mut [Detection], key: SortKey, reverse: bool) {
+    let memory = |detection: &Detection, vsize: bool| {
+        detection.info.memory_status.as_ref().and_then(|memory| {
+            if vsize {
+                memory.virtual_memory
+            } else {
+                memory.resident_memory
+            }
+        })
+    };
+
+    detections.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Pid => a.pid.cmp(&b.pid),
+            SortKey::Rss => memory(a, false).cmp(&memory(b, false)),
+            SortKey::Vsize => memory(a, true).cmp(&memory(b, true)),
+            SortKey::Rings => a.rings.len().cmp(&b.rings.len()),
+            SortKey::Name => a.info.name.cmp(&b.info.name),
+        };
+        ordering.then(a.pid.cmp(&b.pid))
+    });
+
+    if reverse {
+        detections.reverse();
+    }
+}
+
+/// Scans /proc and orders the detections as requested on the command line
+fn scan(options: &Options) -> io::Result<ScanResult> {
+    let started = Instant::now();
+    let mut result = scan_processes(&options.scan_options()).map_err(|e| {
+        io::Error::new(e.kind(), format!("could not read {}: {}", options.proc_root().display(), e))
+    })?;
+    debug!(
+        "scanned {} process(es) under {} in {:?}",
+        result.inventory.len(),
+        options.proc_root().display(),
+        started.elapsed()
+    );
+    sort_detections(&mut result.detections, options.sort_by, options.reverse);
+    Ok(result)
+}
+
+/// Prints how many rings fall into each inferred workload category
+fn print_workload_summary(detections: &[Detection]) {
+    let mut counts = [("disk I/O", 0), ("network I/O", 0), ("mixed", 0), ("idle/unknown", 0)];
+    for ring in detections.iter().flat_map(|detection| &detection.rings) {
+        let bucket = match ring.inferred_workload.as_deref() {
+            Some("disk") => 0,
+            Some("network") => 1,
+            Some("mixed") => 2,
+            _ => 3,
+        };
+        counts[bucket].1 += 1;
+    }
+
+    println!("\nRings by inferred purpose:");
+    for (label, count) in counts {
+        println!("  {:<14} {}", label, count);
+    }
+}
+
+/// Prints the one-line totals that end every text report, for scripts to
+/// match on; processes and rings are zero when no scan was done
+fn print_run_summary(sys_info: &SystemInfo, detections: &[Detection]) {
+    let rings: usize = detections.iter().map(|detection| detection.rings.len()).sum();
+    println!(
+        "\nSUMMARY supported={} min_kernel={} processes={} rings={}",
+        sys_info.io_uring_support,
+        sys_info.min_kernel_version_met,
+        detections.len(),
+        rings
+    );
+}
+
+/// Probes io_uring support and, with `scan`, scans processes as selected on
+/// the command line, then runs the actions that follow a scan
+///
+/// Every output format prints from the Report this returns.
+fn build_report(options: &Options, scan: bool) -> io::Result<Report> {
+    build_report_with_trend(options, scan).map(|(report, _)| report)
+}
+
+/// build_report, also returning the state file trend for the text report
+fn build_report_with_trend(options: &Options, scan: bool) -> io::Result<(Report, Option<Trend>)> {
+    let started = Instant::now();
+    let mut report = if scan {
+        run_with(&options.scan_options())?
+    } else {
+        run_without_scan()?
+    };
+    debug!(
+        "scanned {} process(es) under {} in {:?}",
+        report.inventory.len(),
+        options.proc_root().display(),
+        started.elapsed()
+    );
+    report.system.min_kernel_version_met = kernel_meets(&report.system.kernel_version, options.min_kernel);
+    sort_detections(&mut report.processes, options.sort_by, options.reverse);
+    let trend = if scan && report.supported {
+        after_scan(options, &report)
+    } else {
+        None
+    };
+    Ok((report, trend))
+}
+
+/// Checks if any running processes are using io_uring and prints a report
+fn check_io_uring_usage(options: &Options, report: &Report) {
+    println!("\nChecking if any process is using io_uring...");
+
+    let detections = &report.processes;
+
+    if let Some(processes) = &report.all_processes {
+        print_process_inventory(processes);
+    }
+
+    let exiting: Vec<String> = report
+        .inventory
+        .iter()
+        .filter(|(_, status)| *status == ScanStatus::Exiting)
+        .map(|(pid, _)| pid.to_string())
+        .collect();
+    if !exiting.is_empty() {
+        println!(
+            "Note: io_uring usage is unknown for exiting (zombie or dead) processes: {}",
+            exiting.join(", ")
+        );
+    }
+
+    let permission_denied = report.permission_denied_count;
+    if permission_denied > 0 {
+        println!(
+            "Note: {} processes could not be inspected (run as root for full results)",
+            permission_denied
+        );
+    }
+
+    let vanished = report.vanished_count;
+    if vanished > 0 {
+        println!(
+            "Note: {} process(es) exited during the scan and are not listed",
+            vanished
+        );
+    }
+
+    if detections.is_empty() {
+        match options.pid {
+            Some(pid) if !options.proc_root().join(pid.to_string()).exists() => {
+                println!("PID {} does not exist.", pid);
+            }
+            Some(pid) => println!("PID {} is NOT using io_uring.", pid),
+            None => println!("No processes using io_uring were found."),
+        }
+        return;
+    }
+
+    for detection in detections {
+        print_detection(&options.proc_root(), &report.system, detection, detections);
+    }
+
+    let total = total_memory(detections);
+    println!(
+        "\nTotal: VmSize={} kB RSS={} kB",
+        total.virtual_memory.unwrap_or(0),
+        total.resident_memory.unwrap_or(0)
+    );
+    print_workload_summary(detections);
+
+    if let Some(suppressed) = report.suppressed_duplicates {
+        println!("Suppressed {} duplicate process(es) of an executable already shown", suppressed);
+    }
+}
+
+/// Lists the SQPOLL and io-wq worker threads of every process, which a
+/// syscall-based monitor would not see doing I/O
+fn print_io_uring_threads(options: &Options) {
+    let proc_root = options.proc_root();
+    let name_filter = options.name.as_deref().map(str::to_lowercase);
+    let threads: Vec<(IoUringThread, String)> = find_sqpoll_threads(&proc_root)
+        .into_iter()
+        .filter(|thread| options.pid.is_none_or(|pid| thread.pid == pid))
+        .map(|thread| {
+            let owner = get_process_name(&proc_root, thread.pid).unwrap_or_else(|| String::from("<unknown>"));
+            (thread, owner)
+        })
+        .filter(|(_, owner)| {
+            name_filter.as_ref().is_none_or(|filter| owner.to_lowercase().contains(filter.as_str()))
+        })
+        .collect();
+    println!("\nio_uring kernel threads:");
+    if threads.is_empty() {
+        println!("  none found");
+        return;
+    }
+    for (thread, owner) in &threads {
+        println!(
+            "  TID {:<8} {:<20} owned by PID {} ({})",
+            thread.tid, thread.name, thread.pid, owner
+        );
+    }
+}
+
+/// Reports rings whose inode is held by processes that cannot have
+/// inherited it from one another, which suggests the ring fd was passed
+/// over a unix socket with SCM_RIGHTS
+///
+/// Before kernel 5.12 every ring shares one anonymous inode, so the check
+/// is skipped there.
+fn report_passed_rings(proc_root: &Path, sys_info: &SystemInfo, detections: &[Detection]) {
+    if !ring_inodes_unique(sys_info) {
+        return;
+    }
+
+    let by_inode = group_rings_by_inode(detections);
+    let mut reported = false;
+    for (inode, holders) in &by_inode {
+        let mut pids: Vec<u32> = holders.iter().map(|(detection, _)| detection.pid).collect();
+        pids.dedup();
+        if pids.len() < 2 {
+            continue;
+        }
+
+        if !holders_unrelated(proc_root, holders) {
+            continue;
+        }
+
+        if !reported {
+            println!("\nRings shared by unrelated processes (possibly passed via SCM_RIGHTS):");
+            reported = true;
+        }
+        let holders: Vec<String> = holders
+            .iter()
+            .map(|(detection, fd)| format!("PID {} ({}) fd {}", detection.pid, detection.info.name, fd))
+            .collect();
+        println!("  Ring inode {}: {}", inode, holders.join(", "));
+    }
+}
+
+/// Aggregated io_uring usage of a single user
+#[derive(Debug, Default, Serialize)]
+struct UserUsage {
+    /// User name, or the UID when it has no passwd entry
+    user: String,
+    uid: Option<u32>,
+    processes: usize,
+    rings: usize,
+    /// Total resident memory in kB
+    resident_memory: u64,
+}
+
+/// Totals process count, ring count and RSS per owning user, busiest first
+fn usage_by_user(detections: &[Detection]) -> Vec<UserUsage> {
+    let mut by_uid: Vec<UserUsage> = Vec::new();
+
+    for detection in detections {
+        let uid = detection.info.uid;
+        let index = match by_uid.iter().position(|usage| usage.uid == uid) {
+            Some(index) => index,
+            None => {
+                let user = match uid {
+                    Some(uid) => username_for_uid(uid).unwrap_or_else(|| uid.to_string()),
+                    None => String::from("<unknown>"),
+                };
+                by_uid.push(UserUsage { user, uid, ..UserUsage::default() });
+                by_uid.len() - 1
+            }
+        };
+
+        let usage = &mut by_uid[index];
+        usage.processes += 1;
+        usage.rings += detection.rings.len();
+        usage.resident_memory += detection
+            .info
+            .memory_status
+            .as_ref()
+            .and_then(|memory| memory.resident_memory)
+            .unwrap_or(0);
+    }
+
+    by_uid.sort_by(|a, b| b.rings.cmp(&a.rings).then(b.processes.cmp(&a.processes)));
+    by_uid
+}
+
+/// Prints process count, ring count and RSS per owning user, busiest first
+fn print_usage_by_user(detections: &[Detection]) {
+    println!("\nio_uring usage by user:");
+    println!("  {:<20} {:>10} {:>8} {:>12}", "USER", "PROCESSES", "RINGS", "RSS (kB)");
+    for usage in usage_by_user(detections) {
+        println!(
+            "  {:<20} {:>10} {:>8} {:>12}",
+            usage.user, usage.processes, usage.rings, usage.resident_memory
+        );
+    }
+}
+
+/// When the webhook should be notified
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum WebhookTrigger {
+    /// Any process using io_uring
+    #[default]
+    Any,
+    /// Only high-risk processes, e.g. fileless executables or direct device I/O
+    HighRisk,
+}
+
+/// A detected process as sent to the webhook
+#[derive(Debug, Serialize)]
+struct WebhookProcess<'a> {
+    pid: u32,
+    name: &'a str,
+    exe: Option<String>,
+    uid: Option<u32>,
+    ring_fds: Vec<RawFd>,
+    high_risk: bool,
+}
+
+/// Detection summary posted to the webhook
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    hostname: &'a str,
+    machine_id: Option<&'a str>,
+    kernel_version: &'a str,
+    reason_code: SupportReason,
+    processes_using_io_uring: usize,
+    rings_total: usize,
+    high_risk: bool,
+    processes: Vec<WebhookProcess<'a>>,
+}
+
+/// Posts the detection summary to a webhook if the trigger condition is met
+///
+/// Failures are reported but never fail the scan itself.
+fn notify_webhook(url: &str, trigger: WebhookTrigger, sys_info: &SystemInfo, detections: &[Detection]) {
+    let high_risk = detections.iter().any(is_high_risk);
+    let should_send = match trigger {
+        WebhookTrigger::Any => !detections.is_empty(),
+        WebhookTrigger::HighRisk => high_risk,
+    };
+    if !should_send {
+        return;
+    }
+
+    let payload = WebhookPayload {
+        hostname: &sys_info.hostname,
+        machine_id: sys_info.machine_id.as_deref(),
+        kernel_version: &sys_info.kernel_version,
+        reason_code: sys_info.reason_code,
+        processes_using_io_uring: detections.len(),
+        rings_total: detections.iter().map(|detection| detection.rings.len()).sum(),
+        high_risk,
+        processes: detections
+            .iter()
+            .map(|detection| WebhookProcess {
+                pid: detection.pid,
+                name: &detection.info.name,
+                exe: detection
+                    .info
+                    .exe_path
+                    .as_ref()
+                    .map(|path| path.display().to_string()),
+                uid: detection.info.uid,
+                ring_fds: detection.rings.iter().map(|ring| ring.fd).collect(),
+                high_risk: is_high_risk(detection),
+            })
+            .collect(),
+    };
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout(std::time::Duration::from_secs(10))
+        .build();
+    match agent.post(url).send_json(&payload) {
+        Ok(_) => info!("webhook notified: {}", url),
+        Err(e) => warn!("webhook notification failed: {}", e),
+    }
+}
+
+/// Runs the actions requested alongside the report once a scan has
+/// finished; build_report calls this, so no output format skips them
+///
+/// Returns the state file trend, which only the text report prints.
+fn after_scan(options: &Options, report: &Report) -> Option<Trend> {
+    if let Some(url) = &options.webhook {
+        notify_webhook(url, options.webhook_on, &report.system, &report.processes);
+    }
+
+    let path = options.state_file.as_ref()?;
+    let summary = ScanSummary::new(&report.processes, report.permission_denied_count);
+    match record_scan_summary(path, &summary) {
+        Ok(previous) => Some(Trend { previous, summary }),
+        Err(e) => {
+            warn!("could not update state file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// A scan summary just recorded in the state file, with the one before it
+struct Trend {
+    previous: Option<ScanSummary>,
+    summary: ScanSummary,
+}
+
+/// Maximum number of scan summaries kept in the state file
+const STATE_FILE_MAX_ENTRIES: usize = 100;
+
+/// One line of the rolling state file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScanSummary {
+    /// Seconds since the Unix epoch
+    timestamp: u64,
+    processes: usize,
+    rings: usize,
+    /// Processes that could not be inspected; absent from older state files
+    #[serde(default)]
+    permission_denied_count: usize,
+    /// Progress of the --watch session this entry checkpoints, absent for
+    /// single scans
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    watch: Option<WatchCheckpoint>,
+}
+
+/// What a --watch session has seen so far, saved in the state file so that
+/// a restarted session can carry on from it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WatchCheckpoint {
+    /// When the session started, in seconds since the Unix epoch; it
+    /// identifies the session across restarts
+    started: u64,
+    scans: u64,
+    /// Every PID seen using io_uring during the session, in ascending order
+    seen: Vec<u32>,
+}
+
+impl ScanSummary {
+    /// Summarizes a scan that just finished
+    fn new(detections: &[Detection], permission_denied_count: usize) -> ScanSummary {
+        ScanSummary {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0),
+            processes: detections.len(),
+            rings: detections.iter().map(|detection| detection.rings.len()).sum(),
+            permission_denied_count,
+            watch: None,
+        }
+    }
+}
+
+/// Formats a number of seconds as a short human readable age
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86400 => format!("{}h{}m", s / 3600, (s % 3600) / 60),
+        s => format!("{}d{}h", s / 86400, (s % 86400) / 3600),
+    }
+}
+
+/// Describes how a count changed between two scans
+fn describe_trend(previous: usize, current: usize) -> String {
+    match current.cmp(&previous) {
+        std::cmp::Ordering::Greater => format!("up from {} to {}", previous, current),
+        std::cmp::Ordering::Less => format!("down from {} to {}", previous, current),
+        std::cmp::Ordering::Equal => format!("unchanged at {}", current),
+    }
+}
+
+/// Prints how the latest scan compares with the previous one in the state
+/// file
+fn print_trend(path: &Path, trend: &Trend) {
+    let summary = &trend.summary;
+    println!("\nTrend (from {}):", path.display());
+    if let Some(previous) = &trend.previous {
+        println!(
+            "  Processes: {} since last run {} ago",
+            describe_trend(previous.processes, summary.processes),
+            format_age(summary.timestamp.saturating_sub(previous.timestamp))
+        );
+        println!("  Rings: {}", describe_trend(previous.rings, summary.rings));
+    } else {
+        println!("  No previous scans recorded");
+    }
+}
+
+/// Reads the scan summaries from the JSON lines state file, oldest first,
+/// skipping lines that cannot be parsed
+fn read_scan_summaries(path: &Path) -> io::Result<Vec<ScanSummary>> {
+    match read_to_string(path) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Appends a scan summary to the JSON lines state file, keeping only the
+/// most recent entries, and returns the previous scan's summary
+fn record_scan_summary(path: &Path, summary: &ScanSummary) -> io::Result<Option<ScanSummary>> {
+    let mut entries = read_scan_summaries(path)?;
+    let previous = entries.last().cloned();
+    entries.push(summary.clone());
+    write_scan_summaries(path, &entries)?;
+    Ok(previous)
+}
+
+/// Saves a --watch checkpoint to the state file, replacing the previous
+/// checkpoint of the same session so that a session takes up one entry
+fn record_watch_checkpoint(path: &Path, summary: &ScanSummary) -> io::Result<()> {
+    let mut entries = read_scan_summaries(path)?;
+    let started = summary.watch.as_ref().map(|watch| watch.started);
+    if entries.last().is_some_and(|last| last.watch.as_ref().map(|watch| watch.started) == started) {
+        entries.pop();
+    }
+    entries.push(summary.clone());
+    write_scan_summaries(path, &entries)
+}
+
+/// Rewrites the state file with the most recent of `entries`
+fn write_scan_summaries(path: &Path, entries: &[ScanSummary]) -> io::Result<()> {
+    let skip = entries.len().saturating_sub(STATE_FILE_MAX_ENTRIES);
+
+    let mut contents = String::new();
+    for entry in &entries[skip..] {
+        contents.push_str(&serde_json::to_string(entry)?);
+        contents.push('\n');
+    }
+
+    // Write to a sibling file and rename so a crash never truncates history
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    std::fs::write(&temp_path, contents)?;
+    std::fs::rename(&temp_path, path)
+}
+
+/// Keys the process report can be ordered by
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortKey {
+    #[default]
+    Pid,
+    Rss,
+    Vsize,
+    Rings,
+    Name,
+}
+
+/// Output formats for the scan results
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable report
+    #[default]
+    Text,
+    /// A single InfluxDB line protocol point
+    Influx,
+    /// A Graphviz graph of which processes hold which rings
+    Dot,
+    /// One RFC 5424 syslog message with structured data per detection
+    SyslogRfc5424,
+    /// A prose summary for readers who do not need the details
+    #[value(skip)]
+    Explain,
+    /// The whole run as a single JSON document
+    Json,
+    /// Gauges in the Prometheus text exposition format
+    Prometheus,
+    /// One CSV row per process using io_uring, after a header row
+    Csv,
+}
+
+/// Per-process fields --fields can select for --json and --csv
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+enum Field {
+    Hostname,
+    Pid,
+    Name,
+    Comm,
+    Uid,
+    Username,
+    Exe,
+    Cmdline,
+    Ppid,
+    ContainerId,
+    RingCount,
+    VmKb,
+    RssKb,
+    DetectedVia,
+}
+
+/// The CSV columns when --fields is not given
+const DEFAULT_CSV_FIELDS: &[Field] = &[
+    Field::Hostname,
+    Field::Pid,
+    Field::Name,
+    Field::Uid,
+    Field::Exe,
+    Field::RingCount,
+    Field::VmKb,
+    Field::RssKb,
+];
+
+impl Field {
+    /// The column header and JSON key for the field
+    fn name(self) -> String {
+        self.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default()
+    }
+
+    /// The field's value for a detection, null when it could not be read
+    fn value(self, hostname: &str, detection: &Detection) -> serde_json::Value {
+        let info = &detection.info;
+        let memory = info.memory_status.as_ref();
+        match self {
+            Field::Hostname => hostname.into(),
+            Field::Pid => detection.pid.into(),
+            Field::Name => info.name.clone().into(),
+            Field::Comm => info.comm.clone().into(),
+            Field::Uid => info.uid.into(),
+            Field::Username => info.username.clone().into(),
+            Field::Exe => info.exe_path.as_ref().map(|path| path.to_string_lossy().into_owned()).into(),
+            Field::Cmdline => info.cmdline.clone().into(),
+            Field::Ppid => info.ppid.into(),
+            Field::ContainerId => info.container_id.clone().into(),
+            Field::RingCount => detection.rings.len().into(),
+            Field::VmKb => memory.and_then(|memory| memory.virtual_memory).into(),
+            Field::RssKb => memory.and_then(|memory| memory.resident_memory).into(),
+            Field::DetectedVia => serde_json::to_value(detection.detected_via).unwrap_or_default(),
+        }
+    }
+}
+
+/// Renders a field value as an unescaped CSV cell: empty for null and the
+/// command line joined with spaces
+fn csv_cell(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(items) => items.iter().map(csv_cell).collect::<Vec<_>>().join(" "),
+        other => other.to_string(),
+    }
+}
+
+/// Exit status for a failed run, including invalid arguments
+const EXIT_ERROR: i32 = 3;
+
+/// What a run found, reported as the exit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    /// io_uring is unavailable, or the mode does not scan processes
+    Clean,
+    /// io_uring is available but no process uses it
+    Unused,
+    /// At least one process uses io_uring
+    InUse,
+}
+
+impl Outcome {
+    /// Classifies a scan by whether io_uring was available and whether any
+    /// process was found using it
+    fn of_scan(supported: bool, detections: &[Detection]) -> Outcome {
+        match (supported, detections.is_empty()) {
+            (false, _) => Outcome::Clean,
+            (true, true) => Outcome::Unused,
+            (true, false) => Outcome::InUse,
+        }
+    }
+
+    fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Clean => 0,
+            Outcome::Unused => 1,
+            Outcome::InUse => 2,
+        }
+    }
+}
+
+/// Command line options
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Detects io_uring support and the processes using io_uring",
+    after_help = "Exit status:\n  \
+        0  io_uring is unavailable, or the mode does not scan processes\n  \
+        1  io_uring is available but no process uses it\n  \
+        2  at least one process uses io_uring\n  \
+        3  error, including invalid arguments"
+)]
+struct Options {
+    /// Check the io_uring_setup syscall number for this architecture
+    /// without creating a ring, then exit
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "features_only", "processes_only", "quiet", "format", "json", "prometheus", "csv", "explain",
+            "watch", "watch_pid", "daemon",
+        ]
+    )]
+    dry_probe: bool,
+    /// Print the system information and io_uring features, but do not scan processes
+    #[arg(long, conflicts_with = "processes_only")]
+    features_only: bool,
+    /// Only scan processes, without the system information and feature sections
+    #[arg(long)]
+    processes_only: bool,
+    /// Print bare results for scripts: feature names with --features-only,
+    /// otherwise the PID of each process using io_uring, one per line
+    #[arg(long, short)]
+    quiet: bool,
+    /// Log diagnostics to stderr: -v for progress, -vv for per-scan detail.
+    /// RUST_LOG overrides this
+    #[arg(long, short, action = ArgAction::Count)]
+    verbose: u8,
+    /// Ignore rings with 1-2 SQ entries, which are usually support probes
+    #[arg(long)]
+    hide_probe_rings: bool,
+    /// Order processes by this key
+    #[arg(long, value_enum, value_name = "KEY", default_value_t)]
+    sort_by: SortKey,
+    /// Reverse the sort order, e.g. to list the largest first
+    #[arg(long)]
+    reverse: bool,
+    /// Append a summary of each scan to a JSON lines file and report the
+    /// trend since the previous scan
+    #[arg(long, value_name = "PATH")]
+    state_file: Option<PathBuf>,
+    /// Print process, ring and RSS totals per owning user, or add them to
+    /// the --json document
+    #[arg(long)]
+    count_by_user: bool,
+    /// Run the scan under ptrace and list the distinct syscalls it made
+    /// (requires permission to ptrace a child process)
+    #[arg(long)]
+    trace_self: bool,
+    /// POST a JSON detection summary to URL after the scan
+    #[arg(long, value_name = "URL")]
+    webhook: Option<String>,
+    /// Notify on any detection or only on high-risk ones
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t)]
+    webhook_on: WebhookTrigger,
+    /// List every scanned PID with whether it uses io_uring, in the text
+    /// report, --json or --csv
+    #[arg(long)]
+    include_all_processes: bool,
+    /// Output format for the results
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t)]
+    format: OutputFormat,
+    /// Same as --format json
+    #[arg(long)]
+    json: bool,
+    /// Write the --json document to DIR/<hostname>-<timestamp>.json instead
+    /// of stdout, creating DIR if needed
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["format", "prometheus", "csv", "explain", "quiet"])]
+    output_dir: Option<PathBuf>,
+    /// Split the --json output into documents of at most N processes each,
+    /// printed one per line, instead of a single document
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["output_dir", "watch", "watch_pid", "daemon"]
+    )]
+    batch_size: Option<u64>,
+    /// Only include these per-process fields in --json and --csv, in the
+    /// order given
+    #[arg(long, value_enum, value_name = "FIELD,...", value_delimiter = ',')]
+    fields: Option<Vec<Field>>,
+    /// Same as --format prometheus
+    #[arg(long)]
+    prometheus: bool,
+    /// Same as --format csv
+    #[arg(long)]
+    csv: bool,
+    /// Describe the results in plain sentences instead of a report
+    #[arg(long)]
+    explain: bool,
+    /// Leave the detector's own process out of the scan
+    #[arg(long, alias = "exclude-self")]
+    ignore_self: bool,
+    /// Show only the first process of each distinct executable
+    #[arg(long)]
+    once_per_exe: bool,
+    /// Print every field returned by the io_uring_setup probe
+    #[arg(long)]
+    dump_params: bool,
+    #[arg(
+        long,
+        help = format!(
+            "Skip processes with fewer than {} fds unless their name is on a known \
+             io_uring user allowlist (faster, but can miss rings)",
+            FAST_MIN_FDS
+        )
+    )]
+    fast: bool,
+    /// Print the target, profile and syscall number this binary was built
+    /// with, then exit
+    #[arg(long)]
+    build_info: bool,
+    /// Do not color the text report, even on a terminal (also set by NO_COLOR)
+    #[arg(long)]
+    no_color: bool,
+    /// Only report processes whose cgroup path contains SUBSTRING (repeatable)
+    #[arg(long, value_name = "SUBSTRING")]
+    cgroup_filter: Vec<String>,
+    /// Leave out processes whose cgroup path contains SUBSTRING (repeatable)
+    #[arg(long, value_name = "SUBSTRING")]
+    cgroup_exclude: Vec<String>,
+    /// Hold an exclusive lock on PATH during the scan and exit if another
+    /// scan already holds it
+    #[arg(long, value_name = "PATH")]
+    lock_file: Option<PathBuf>,
+    /// With --lock-file, wait for the other scan instead of exiting
+    #[arg(long, requires = "lock_file")]
+    lock_wait: bool,
+    /// Read processes from PATH/proc instead of /proc, e.g. a container's
+    /// procfs bind-mounted on the host
+    #[arg(long, value_name = "PATH")]
+    root: Option<PathBuf>,
+    /// Read processes from a tar archive of /proc on stdin, e.g. one
+    /// streamed from another host over SSH (see the README for what it
+    /// must contain)
+    #[arg(long, conflicts_with_all = ["root", "watch", "watch_pid", "daemon"])]
+    stdin_tar: bool,
+    /// Find io_uring users with a BPF task_file iterator instead of reading
+    /// every process's fds, falling back to /proc when BPF is unavailable
+    #[cfg(feature = "bpf")]
+    #[arg(long, conflicts_with_all = ["root", "stdin_tar", "pid"])]
+    bpf: bool,
+    /// Judge the kernel release against MAJOR.MINOR instead of 5.1, e.g. to
+    /// check that hosts meet the release a required feature appeared in
+    #[arg(long, value_name = "MAJOR.MINOR", value_parser = parse_min_kernel, default_value = "5.1")]
+    min_kernel: (u32, u32),
+    /// Inspect only PID and report whether it uses io_uring
+    #[arg(long, value_name = "PID")]
+    pid: Option<u32>,
+    /// Inspect only processes whose name (comm) contains SUBSTRING, ignoring case
+    #[arg(long, value_name = "SUBSTRING")]
+    name: Option<String>,
+    /// Report io_uring fds opening and closing in PID until it exits
+    #[arg(long, value_name = "PID")]
+    watch_pid: Option<u32>,
+    /// Rescan every SECONDS and report processes that start or stop using
+    /// io_uring, until interrupted with Ctrl-C; with --state-file the
+    /// session is checkpointed there and resumed on restart
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    watch: Option<u64>,
+    /// Run as a monitoring agent: rescan every 10 seconds (or --watch
+    /// SECONDS) and append io_uring start and stop events to --logfile
+    #[arg(long, requires = "logfile")]
+    daemon: bool,
+    /// JSON-lines event log for --daemon; reopened on SIGHUP
+    #[arg(long, value_name = "PATH", requires = "daemon")]
+    logfile: Option<PathBuf>,
+}
+
+impl Options {
+    /// The process scan settings selected on the command line
+    fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            hide_probe_rings: self.hide_probe_rings,
+            ignore_self: self.ignore_self,
+            fast: self.fast,
+            cgroup_filter: self.cgroup_filter.clone(),
+            cgroup_exclude: self.cgroup_exclude.clone(),
+            pid: self.pid,
+            name: self.name.clone(),
+            once_per_exe: self.once_per_exe,
+            include_all_processes: self.include_all_processes,
+            root: self.root.clone(),
+            #[cfg(feature = "bpf")]
+            bpf: self.bpf,
+        }
+    }
+
+    /// The procfs processes are read from
+    fn proc_root(&self) -> PathBuf {
+        proc_root(self.root.as_deref())
+    }
+
+    /// Log level selected by -v and --quiet
+    fn log_level(&self) -> log::LevelFilter {
+        match (self.quiet, self.verbose) {
+            (true, _) => log::LevelFilter::Error,
+            (false, 0) => log::LevelFilter::Warn,
+            (false, 1) => log::LevelFilter::Info,
+            (false, 2) => log::LevelFilter::Debug,
+            (false, _) => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// Parses the --min-kernel value; a bare major version means minor 0
+fn parse_min_kernel(value: &str) -> Result<(u32, u32), String> {
+    parse_kernel_version(value).ok_or_else(|| format!("expected MAJOR.MINOR, got {:?}", value))
+}
+
+/// Gathers the system information, judging the kernel release against
+/// the --min-kernel threshold
+fn host_info(options: &Options) -> io::Result<SystemInfo> {
+    let mut sys_info = system_info()?;
+    sys_info.min_kernel_version_met = kernel_meets(&sys_info.kernel_version, options.min_kernel);
+    Ok(sys_info)
+}
+
+/// Parses the command line arguments, exiting with usage on errors
+fn parse_args() -> Options {
+    let mut options = Options::try_parse().unwrap_or_else(|e| {
+        // --help and --version also arrive here, and are not failures
+        let status = if e.use_stderr() { EXIT_ERROR } else { 0 };
+        let _ = e.print();
+        std::process::exit(status);
+    });
+    if options.json || options.output_dir.is_some() {
+        options.format = OutputFormat::Json;
+    }
+    if options.prometheus {
+        options.format = OutputFormat::Prometheus;
+    }
+    if options.csv {
+        options.format = OutputFormat::Csv;
+    }
+    if options.explain {
+        options.format = OutputFormat::Explain;
+    }
+    // The other formats have no place for the per-user table
+    if options.count_by_user && (options.quiet || !matches!(options.format, OutputFormat::Text | OutputFormat::Json)) {
+        let _ = Options::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--count-by-user is only available with the text report and --json",
+            )
+            .print();
+        std::process::exit(EXIT_ERROR);
+    }
+    if options.include_all_processes
+        && (options.quiet || !matches!(options.format, OutputFormat::Text | OutputFormat::Json | OutputFormat::Csv))
+    {
+        let _ = Options::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--include-all-processes is only available with the text report, --json and --csv",
+            )
+            .print();
+        std::process::exit(EXIT_ERROR);
+    }
+    if options.batch_size.is_some() && (options.quiet || !matches!(options.format, OutputFormat::Json)) {
+        let _ = Options::command()
+            .error(ErrorKind::ArgumentConflict, "--batch-size is only available with --json")
+            .print();
+        std::process::exit(EXIT_ERROR);
+    }
+    if options.fields.is_some() && (options.quiet || !matches!(options.format, OutputFormat::Json | OutputFormat::Csv)) {
+        let _ = Options::command()
+            .error(ErrorKind::ArgumentConflict, "--fields is only available with --json and --csv")
+            .print();
+        std::process::exit(EXIT_ERROR);
+    }
+    options
+}
+
+/// Exit status of the traced child when PTRACE_TRACEME is refused
+#[cfg(target_os = "linux")]
+const PTRACE_DENIED_STATUS: i32 = 126;
+
+/// Request for PTRACE_GET_SYSCALL_INFO, not exported by libc for musl
+#[cfg(target_os = "linux")]
+const PTRACE_GET_SYSCALL_INFO: libc::c_int = 0x420e;
+
+/// Value of ptrace_syscall_info.op for a syscall entry stop
+#[cfg(target_os = "linux")]
+const PTRACE_SYSCALL_INFO_ENTRY: u8 = 1;
+
+/// Entry-stop view of the kernel's struct ptrace_syscall_info
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Debug, Default)]
+struct PtraceSyscallInfo {
+    op: u8,
+    pad: [u8; 3],
+    arch: u32,
+    instruction_pointer: u64,
+    stack_pointer: u64,
+    nr: u64,
+    args: [u64; 6],
+    ret_data: u32,
+}
+
+/// Builds a (number, name) table from libc syscall constants
+macro_rules! syscall_table {
+    ($($name:ident),* $(,)?) => {
+        &[$((libc::$name as u64, stringify!($name)),)*]
+    };
+}
+
+/// Names of the syscalls the detector is expected to make
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+const SYSCALL_NAMES: &[(u64, &str)] = syscall_table![
+    SYS_read, SYS_write, SYS_close, SYS_openat, SYS_fstat, SYS_newfstatat, SYS_statx,
+    SYS_lseek, SYS_getdents64, SYS_readlinkat, SYS_mmap, SYS_munmap, SYS_mprotect,
+    SYS_mremap, SYS_madvise, SYS_brk, SYS_ioctl, SYS_fcntl, SYS_uname, SYS_getuid,
+    SYS_getpid, SYS_gettid, SYS_rt_sigaction, SYS_rt_sigprocmask, SYS_rt_sigreturn,
+    SYS_sigaltstack, SYS_futex, SYS_set_tid_address, SYS_set_robust_list, SYS_rseq,
+    SYS_prlimit64, SYS_getrandom, SYS_sched_getaffinity, SYS_prctl, SYS_socket,
+    SYS_connect, SYS_io_uring_setup, SYS_io_uring_enter, SYS_io_uring_register,
+    SYS_ptrace, SYS_kill, SYS_tgkill, SYS_exit, SYS_exit_group, SYS_renameat2,
+    SYS_clock_gettime, SYS_ppoll, SYS_faccessat,
+];
+
+/// Names of the syscalls the detector is expected to make
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+const SYSCALL_NAMES: &[(u64, &str)] = &[];
+
+/// Looks up the name of a syscall number for the compiled architecture
+fn syscall_name(nr: u64) -> Option<&'static str> {
+    #[cfg(target_arch = "x86_64")]
+    let legacy: &[(u64, &str)] = syscall_table![
+        SYS_open, SYS_stat, SYS_lstat, SYS_access, SYS_readlink, SYS_poll,
+        SYS_arch_prctl, SYS_getdents, SYS_rename, SYS_renameat,
+    ];
+    #[cfg(not(target_arch = "x86_64"))]
+    let legacy: &[(u64, &str)] = &[];
+
+    SYSCALL_NAMES
+        .iter()
+        .chain(legacy)
+        .find(|(number, _)| *number == nr)
+        .map(|(_, name)| name.trim_start_matches("SYS_"))
+}
+
+/// Runs the selected mode in a ptrace'd child and prints the distinct
+/// syscalls it and its threads made
+///
+/// Requires permission to ptrace a child process, which seccomp profiles or
+/// Yama's ptrace_scope=3 may deny.
+#[cfg(target_os = "linux")]
+fn trace_self(options: &Options) -> io::Result<Outcome> {
+    use std::collections::BTreeMap;
+    use std::io::Write;
+
+    let child = unsafe { libc::fork() };
+    if child < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if child == 0 {
+        // Child: stop until the parent has attached, then run the mode
+        let null = std::ptr::null_mut::<libc::c_void>();
+        if unsafe { libc::ptrace(libc::PTRACE_TRACEME, 0, null, null) } < 0 {
+            eprintln!("Error: ptrace is not permitted: {}", io::Error::last_os_error());
+            unsafe { libc::_exit(PTRACE_DENIED_STATUS) };
+        }
+        unsafe { libc::raise(libc::SIGSTOP) };
+        let code = match run_mode(options) {
+            Ok(outcome) => outcome.exit_code(),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                EXIT_ERROR
+            }
+        };
+        let _ = io::stdout().flush();
+        std::process::exit(code);
+    }
+
+    let mut status = 0;
+    let mut counts: BTreeMap<u64, usize> = BTreeMap::new();
+    let null = std::ptr::null_mut::<libc::c_void>();
+
+    if unsafe { libc::waitpid(child, &mut status, 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == PTRACE_DENIED_STATUS {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "could not trace the scan; ptrace permission is required",
+        ));
+    }
+    if !libc::WIFSTOPPED(status) {
+        return Err(io::Error::other("traced child exited before it could be traced"));
+    }
+    let trace_options = (libc::PTRACE_O_TRACESYSGOOD | libc::PTRACE_O_EXITKILL | libc::PTRACE_O_TRACECLONE) as usize;
+    unsafe {
+        libc::ptrace(libc::PTRACE_SETOPTIONS, child, null, trace_options as *mut libc::c_void);
+    }
+
+    // Threads are attached automatically through PTRACE_O_TRACECLONE and
+    // start with a SIGSTOP that must not be delivered to them; it can be
+    // seen before the clone event, so threads are recognized by it instead
+    let mut tid = child;
+    let mut signal = 0;
+    let mut threads: HashSet<libc::pid_t> = HashSet::from([child]);
+    loop {
+        // The thread may have exited since it stopped
+        unsafe { libc::ptrace(libc::PTRACE_SYSCALL, tid, null, signal as *mut libc::c_void) };
+        tid = unsafe { libc::waitpid(-1, &mut status, libc::__WALL) };
+        if tid < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        signal = 0;
+        if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+            if tid == child {
+                break;
+            }
+            threads.remove(&tid);
+            continue;
+        }
+
+        let stop_signal = libc::WSTOPSIG(status);
+        if stop_signal == (libc::SIGTRAP | 0x80) {
+            let mut info = PtraceSyscallInfo::default();
+            let ret = unsafe {
+                libc::ptrace(
+                    PTRACE_GET_SYSCALL_INFO as _,
+                    tid,
+                    std::mem::size_of::<PtraceSyscallInfo>(),
+                    &mut info as *mut PtraceSyscallInfo,
+                )
+            };
+            if ret > 0 && info.op == PTRACE_SYSCALL_INFO_ENTRY {
+                *counts.entry(info.nr).or_insert(0) += 1;
+            }
+        } else if stop_signal == libc::SIGSTOP && threads.insert(tid) {
+            // The initial stop of a new thread
+        } else if stop_signal != libc::SIGTRAP {
+            // Deliver signals that were not caused by tracing
+            signal = stop_signal;
+        }
+    }
+
+    println!("\nSyscalls made by the detector ({} distinct):", counts.len());
+    for (nr, count) in &counts {
+        match syscall_name(*nr) {
+            Some(name) => println!("  {:<20} {:>6}", name, count),
+            None => println!("  {:<20} {:>6}", format!("syscall {}", nr), count),
+        }
+    }
+
+    match libc::WIFEXITED(status).then(|| libc::WEXITSTATUS(status)) {
+        Some(0) => Ok(Outcome::Clean),
+        Some(1) => Ok(Outcome::Unused),
+        Some(2) => Ok(Outcome::InUse),
+        _ => Err(io::Error::other("the traced run failed")),
+    }
+}
+
+/// Tracing the detector's own syscalls is only supported on Linux
+#[cfg(not(target_os = "linux"))]
+fn trace_self(_options: &Options) -> io::Result<Outcome> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--trace-self is only supported on Linux",
+    ))
+}
+
+/// How often --watch-pid polls the process's fds
+const WATCH_PID_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Polls a single process and prints an event whenever an io_uring fd
+/// appears or disappears, until the process exits
+fn watch_pid(proc_root: &Path, pid: u32, name: &str) -> io::Result<()> {
+    info!("watching PID {} ({}) for io_uring fds, Ctrl-C to stop", pid, name);
+
+    let mut known: Vec<(u32, RawFd, Option<u64>)> = Vec::new();
+    loop {
+        let exited = !proc_root.join(pid.to_string()).exists()
+            || matches!(get_process_state(proc_root, pid), Some('Z' | 'X'));
+        let current = if exited {
+            Vec::new()
+        } else {
+            // An unreadable fd directory means the process is going away
+            ring_fds_with_inodes(proc_root, pid).unwrap_or_default()
+        };
+
+        let timestamp = format_rfc3339(SystemTime::now());
+        // Fds of a task with its own fd table are named with the task
+        let describe = |&(tid, fd, inode): &(u32, RawFd, Option<u64>)| {
+            let task = if tid == pid { String::new() } else { format!(" of task {}", tid) };
+            format!("ring fd {}{} (inode {})", fd, task, inode.map_or("?".into(), |i| i.to_string()))
+        };
+        for ring in known.iter().filter(|ring| !current.contains(ring)) {
+            println!("{} closed {}", timestamp, describe(ring));
+        }
+        for ring in current.iter().filter(|ring| !known.contains(ring)) {
+            println!("{} opened {}", timestamp, describe(ring));
+        }
+        known = current;
+
+        if exited {
+            println!("{} PID {} exited", timestamp, pid);
+            return Ok(());
+        }
+        std::thread::sleep(WATCH_PID_INTERVAL);
+    }
+}
+
+/// Set by the SIGINT and SIGTERM handler to stop --watch and --daemon
+static WATCH_INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_watch_interrupt(_signal: libc::c_int) {
+    WATCH_INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Makes SIGINT and SIGTERM set WATCH_INTERRUPTED instead of killing the
+/// process, so a polling loop can finish its iteration and shut down cleanly
+fn stop_on_interrupt() {
+    unsafe {
+        let interrupt = handle_watch_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, interrupt);
+        libc::signal(libc::SIGTERM, interrupt);
+    }
+}
+
+/// How often a --watch session is saved to the state file when nothing new
+/// was seen
+const WATCH_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The --watch session recorded last in the state file, if the state file
+/// ends with one
+fn resumable_watch(options: &Options) -> Option<WatchCheckpoint> {
+    let path = options.state_file.as_ref()?;
+    match read_scan_summaries(path) {
+        Ok(mut entries) => entries.pop()?.watch,
+        Err(e) => {
+            warn!("could not read state file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Rescans /proc every `seconds` and prints the processes that started or
+/// stopped using io_uring since the previous scan, until SIGINT or SIGTERM
+///
+/// A signal is only acted on between scans, so the final summary always
+/// covers complete scans. With --state-file the session is checkpointed
+/// whenever a new process is seen, at least every minute and on exit, and
+/// a session that was killed is resumed from its last checkpoint, keeping
+/// its distinct-process count.
+fn watch(options: &Options, seconds: u64) -> io::Result<()> {
+    stop_on_interrupt();
+    info!("scanning for io_uring users every {}s, Ctrl-C or SIGTERM to stop", seconds);
+
+    let mut known: HashSet<u32> = HashSet::new();
+    let mut names: HashMap<u32, String> = HashMap::new();
+    let mut seen: HashSet<u32> = HashSet::new();
+    let mut scans = 0u64;
+    let mut started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    if let Some(checkpoint) = resumable_watch(options) {
+        info!(
+            "resuming the watch session started at {} after {} scan(s) with {} distinct process(es)",
+            format_rfc3339(UNIX_EPOCH + Duration::from_secs(checkpoint.started)),
+            checkpoint.scans,
+            checkpoint.seen.len()
+        );
+        started = checkpoint.started;
+        scans = checkpoint.scans;
+        seen.extend(checkpoint.seen);
+    }
+    let mut last_checkpoint: Option<Instant> = None;
+    let mut summary = None;
+
+    while !WATCH_INTERRUPTED.load(Ordering::SeqCst) {
+        let result = scan(options)?;
+        let detections = &result.detections;
+        scans += 1;
+        debug!("scan {} found {} process(es) using io_uring", scans, detections.len());
+
+        let timestamp = format_rfc3339(SystemTime::now());
+        let current: HashSet<u32> = detections.iter().map(|detection| detection.pid).collect();
+        let mut stopped: Vec<&u32> = known.difference(&current).collect();
+        stopped.sort_unstable();
+        for pid in stopped {
+            let name = names.remove(pid).unwrap_or_default();
+            println!("{} PID {} ({}) stopped using io_uring", timestamp, pid, name);
+        }
+        for detection in detections.iter().filter(|detection| !known.contains(&detection.pid)) {
+            println!(
+                "{} PID {} ({}) started using io_uring with {} ring(s)",
+                timestamp,
+                detection.pid,
+                detection.info.name,
+                detection.rings.len()
+            );
+            names.insert(detection.pid, detection.info.name.clone());
+        }
+        let grew = current.iter().any(|pid| !seen.contains(pid));
+        seen.extend(&current);
+        known = current;
+
+        let mut checkpoint = ScanSummary::new(detections, result.permission_denied_count());
+        let mut seen_pids: Vec<u32> = seen.iter().copied().collect();
+        seen_pids.sort_unstable();
+        checkpoint.watch = Some(WatchCheckpoint { started, scans, seen: seen_pids });
+        let due = last_checkpoint.is_none_or(|at| at.elapsed() >= WATCH_CHECKPOINT_INTERVAL);
+        if let Some(path) = options.state_file.as_ref().filter(|_| grew || due) {
+            save_watch_checkpoint(path, &checkpoint);
+            last_checkpoint = Some(Instant::now());
+        }
+        summary = Some(checkpoint);
+
+        sleep_unless_interrupted(seconds);
+    }
+
+    if let (Some(path), Some(summary)) = (&options.state_file, &summary) {
+        save_watch_checkpoint(path, summary);
+    }
+    println!(
+        "\nStopped after {} scan(s); {} distinct process(es) used io_uring",
+        scans,
+        seen.len()
+    );
+    Ok(())
+}
+
+/// Saves a --watch checkpoint, warning rather than stopping the session when
+/// the state file cannot be written
+fn save_watch_checkpoint(path: &Path, summary: &ScanSummary) {
+    if let Err(e) = record_watch_checkpoint(path, summary) {
+        warn!("could not update state file {}: {}", path.display(), e);
+    }
+}
+
+/// Sleeps for `seconds` in short steps, so that an interrupt is noticed
+/// promptly
+fn sleep_unless_interrupted(seconds: u64) {
+    let deadline = Instant::now() + Duration::from_secs(seconds);
+    while !WATCH_INTERRUPTED.load(Ordering::SeqCst) && Instant::now() < deadline {
+        std::thread::sleep(WATCH_PID_INTERVAL.min(deadline - Instant::now()));
+    }
+}
 
-/// Structure representing io_uring parameters
-#[repr(C)]
-#[derive(Debug, Default)]
-struct IoUringParams {
-    sq_entries: u32,
-    cq_entries: u32,
-    flags: u32,
-    sq_thread_cpu: u32,
-    sq_thread_idle: u32,
-    features: u32,
-    wq_fd: u32,
-    resv: [u32; 3],
-    sq_off: IoSqringOffsets,
-    cq_off: IoCqringOffsets,
-}
-
-/// Structure representing submission queue offsets
-#[repr(C)]
-#[derive(Debug, Default)]
-struct IoSqringOffsets {
-    head: u32,
-    tail: u32,
-    ring_mask: u32,
-    ring_entries: u32,
-    flags: u32,
-    dropped: u32,
-    array: u32,
-    resv1: u32,
-    resv2: u64,
-}
-
-/// Structure representing completion queue offsets
-#[repr(C)]
-#[derive(Debug, Default)]
-struct IoCqringOffsets {
-    head: u32,
-    tail: u32,
-    ring_mask: u32,
-    ring_entries: u32,
-    overflow: u32,
-    cqes: u32,
-    flags: u32,
-    resv1: u32,
-    resv2: u64,
-}
-
-/// Feature flags for io_uring
-const IO_URING_FEATURES: &[(u32, &str)] = &[
-    (1 << 0, "IORING_FEAT_SINGLE_MMAP"),
-    (1 << 1, "IORING_FEAT_NODROP"),
-    (1 << 2, "IORING_FEAT_SUBMIT_STABLE"),
-    (1 << 3, "IORING_FEAT_RW_CUR_POS"),
-    (1 << 4, "IORING_FEAT_CUR_PERSONALITY"),
-    (1 << 5, "IORING_FEAT_FAST_POLL"),
-    (1 << 6, "IORING_FEAT_POLL_32BITS"),
-    (1 << 7, "IORING_FEAT_SQPOLL_NONFIXED"),
-    (1 << 8, "IORING_FEAT_ENTER_EXT_ARG"),
-    (1 << 9, "IORING_FEAT_REG_RW"),
-    (1 << 10, "IORING_FEAT_SAFE_LINK"),
-    (1 << 11, "IORING_FEAT_FAST_POLL_FULL"),
-    (1 << 12, "IORING_FEAT_CQE_SKIP"),
-];
+/// Scan interval for --daemon when --watch does not give one
+const DAEMON_INTERVAL_SECS: u64 = 10;
+
+/// Set by the SIGHUP handler to make --daemon reopen its log file
+static LOGFILE_REOPEN: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_logfile_reopen(_signal: libc::c_int) {
+    LOGFILE_REOPEN.store(true, Ordering::SeqCst);
+}
+
+/// One line of the --daemon log file
+#[derive(Debug, Serialize)]
+struct DaemonEvent<'a> {
+    /// RFC 3339 time of the scan that noticed the change
+    timestamp: &'a str,
+    /// "started" or "stopped" using io_uring
+    event: &'static str,
+    pid: u32,
+    name: &'a str,
+    /// Full details of the process, for started events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detection: Option<&'a Detection>,
+}
+
+/// Opens the --daemon log file for appending, creating it if needed
+fn open_logfile(path: &Path) -> io::Result<File> {
+    std::fs::OpenOptions::new().create(true).append(true).open(path).map_err(|e| {
+        io::Error::new(e.kind(), format!("could not open {}: {}", path.display(), e))
+    })
+}
+
+/// Appends an event to the log file as a single JSON line
+fn write_event(log: &mut File, event: &DaemonEvent) -> io::Result<()> {
+    use std::io::Write;
 
-/// Structure to hold system information
-#[derive(Debug)]
-struct SystemInfo {
-    architecture: String,
-    kernel_version: String,
-    io_uring_support: bool,
-    min_kernel_version_met: bool,
+    // One write per line, so lines from concurrent writers do not interleave
+    let mut line = serde_json::to_vec(event)?;
+    line.push(b'\n');
+    log.write_all(&line)
 }
 
-impl Default for SystemInfo {
-    fn default() -> Self {
-        SystemInfo {
-            architecture: String::from("unknown"),
-            kernel_version: String::from("unknown"),
-            io_uring_support: false,
-            min_kernel_version_met: false,
+/// Runs the watch loop as a monitoring agent, appending a JSON line to
+/// the log file whenever a process starts or stops using io_uring
+///
+/// Nothing is printed to stdout. SIGHUP reopens the log file so it can be
+/// rotated, and SIGINT or SIGTERM stops the loop.
+fn daemon(options: &Options, path: &Path) -> io::Result<()> {
+    stop_on_interrupt();
+    unsafe {
+        libc::signal(libc::SIGHUP, handle_logfile_reopen as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+    let seconds = options.watch.unwrap_or(DAEMON_INTERVAL_SECS);
+    let mut log = open_logfile(path)?;
+    info!("logging io_uring events to {} every {}s", path.display(), seconds);
+
+    let mut known: HashMap<u32, String> = HashMap::new();
+    while !WATCH_INTERRUPTED.load(Ordering::SeqCst) {
+        if LOGFILE_REOPEN.swap(false, Ordering::SeqCst) {
+            log = open_logfile(path)?;
+            info!("reopened {}", path.display());
+        }
+
+        let detections = scan(options)?.detections;
+        let timestamp = format_rfc3339(SystemTime::now());
+        let mut stopped: Vec<(&u32, &String)> = known
+            .iter()
+            .filter(|(pid, _)| !detections.iter().any(|detection| detection.pid == **pid))
+            .collect();
+        stopped.sort_unstable();
+        for (pid, name) in stopped {
+            let event = DaemonEvent { timestamp: &timestamp, event: "stopped", pid: *pid, name, detection: None };
+            write_event(&mut log, &event)?;
+        }
+        for detection in detections.iter().filter(|detection| !known.contains_key(&detection.pid)) {
+            let event = DaemonEvent {
+                timestamp: &timestamp,
+                event: "started",
+                pid: detection.pid,
+                name: &detection.info.name,
+                detection: Some(detection),
+            };
+            write_event(&mut log, &event)?;
         }
+        known = detections
+            .iter()
+            .map(|detection| (detection.pid, detection.info.name.clone()))
+            .collect();
+
+        sleep_unless_interrupted(seconds);
     }
+
+    info!("stopped logging to {}", path.display());
+    Ok(())
 }
 
-/// Get system information including architecture and kernel version
-fn get_system_info() -> io::Result<SystemInfo> {
-    #[cfg(target_os = "linux")]
-    {
-        let mut uts = unsafe { std::mem::zeroed::<utsname>() };
-        if unsafe { uname(&mut uts) } == 0 {
-            let arch = unsafe { std::ffi::CStr::from_ptr(uts.machine.as_ptr()) }
-                .to_string_lossy()
-                .into_owned();
-            let kernel = unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) }
-                .to_string_lossy()
-                .into_owned();
-            
-            // Check if kernel version meets minimum requirement (5.1 or higher)
-            let version_parts: Vec<u32> = kernel
-                .split('.')
-                .take(2)
-                .filter_map(|s| s.parse().ok())
-                .collect();
-            
-            let min_version_met = if version_parts.len() >= 2 {
-                let major = version_parts[0];
-                let minor = version_parts[1];
-                major > 5 || (major == 5 && minor >= 1)
-            } else {
-                false
-            };
+/// Takes an exclusive flock on the lock file, creating it if needed, and
+/// records the detector's PID in it
+///
+/// Returns None if another process holds the lock and `wait` is false.
+fn acquire_scan_lock(path: &Path, wait: bool) -> io::Result<Option<File>> {
+    use std::io::{Seek, Write};
+    use std::os::fd::AsRawFd;
 
-            Ok(SystemInfo {
-                architecture: arch,
-                kernel_version: kernel,
-                io_uring_support: false, // Will be set later
-                min_kernel_version_met: min_version_met,
-            })
-        } else {
-            Err(io::Error::last_os_error())
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    let operation = if wait { libc::LOCK_EX } else { libc::LOCK_EX | libc::LOCK_NB };
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::WouldBlock {
+            return Ok(None);
         }
+        return Err(err);
     }
 
-    #[cfg(not(target_os = "linux"))]
-    {
-        Ok(SystemInfo::default())
+    file.set_len(0)?;
+    file.rewind()?;
+    writeln!(file, "{}", std::process::id())?;
+    Ok(Some(file))
+}
+
+/// A /proc tree unpacked from --stdin-tar into a private directory, which
+/// is removed again when this is dropped
+struct ProcArchive {
+    root: PathBuf,
+}
+
+impl ProcArchive {
+    /// Unpacks a tar stream whose members are named proc/<pid>/..., as
+    /// `tar -c /proc/...` writes them
+    ///
+    /// Directories are created writable whatever mode the archive gives
+    /// them, so that the tree can be removed afterwards.
+    fn unpack(reader: impl io::Read) -> io::Result<ProcArchive> {
+        let root = std::env::temp_dir().join(format!("io_uring_detector-{}", std::process::id()));
+        DirBuilder::new().mode(0o700).create(&root)?;
+        let archive = ProcArchive { root };
+
+        for entry in tar::Archive::new(reader).entries()? {
+            let mut entry = entry?;
+            if entry.header().entry_type().is_dir() {
+                let path = entry.path()?.into_owned();
+                if path.components().all(|part| matches!(part, std::path::Component::Normal(_))) {
+                    create_dir_all(archive.root.join(path))?;
+                }
+                continue;
+            }
+            // Refuses members that would land outside the directory
+            entry.unpack_in(&archive.root)?;
+        }
+
+        if !archive.root.join("proc").is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the archive on stdin holds no proc directory",
+            ));
+        }
+        Ok(archive)
     }
 }
 
-/// Attempts to detect if io_uring is supported on the system
-/// Returns Some(IoUringParams) if supported, None otherwise
-fn detect_io_uring_support() -> io::Result<Option<IoUringParams>> {
-    #[cfg(target_os = "linux")]
-    {
-        let mut params: IoUringParams = Default::default();
-        let entries: c_uint = 1;
-
-        let ret = unsafe {
-            syscall(
-                SYS_io_uring_setup,
-                entries,
-                &mut params as *mut IoUringParams,
-            )
-        };
+impl Drop for ProcArchive {
+    fn drop(&mut self) {
+        if let Err(e) = remove_dir_all(&self.root) {
+            warn!("Could not remove {}: {}", self.root.display(), e);
+        }
+    }
+}
+
+fn main() {
+    let mut options = parse_args();
+    env_logger::Builder::new()
+        .filter_level(options.log_level())
+        .parse_default_env()
+        .init();
+    COLOR.store(use_color(&options), Ordering::Relaxed);
+
+    if options.build_info {
+        print_build_info();
+        return;
+    }
 
-        if ret >= 0 {
-            unsafe {
-                libc::close(ret as RawFd);
+    // Held until the run finishes so the whole scan runs under the lock
+    let lock = match &options.lock_file {
+        Some(path) => match acquire_scan_lock(path, options.lock_wait) {
+            Ok(Some(lock)) => Some(lock),
+            Ok(None) => {
+                let holder = read_to_string(path).unwrap_or_default();
+                match holder.trim() {
+                    "" => eprintln!("Error: another scan holds {}", path.display()),
+                    pid => eprintln!("Error: another scan (PID {}) holds {}", pid, path.display()),
+                }
+                eprintln!("Use --lock-wait to wait for it to finish");
+                std::process::exit(EXIT_ERROR);
             }
-            Ok(Some(params))
-        } else {
-            let err = io::Error::last_os_error();
-            if err.raw_os_error() == Some(libc::ENOSYS) {
-                Ok(None) // System call not implemented
-            } else {
-                Err(err) // Other error occurred
+            Err(e) => {
+                eprintln!("Error: could not lock {}: {}", path.display(), e);
+                std::process::exit(EXIT_ERROR);
+            }
+        },
+        None => None,
+    };
+
+    // Removed before exiting, which skips destructors
+    let archive = if options.stdin_tar {
+        match ProcArchive::unpack(io::stdin().lock()) {
+            Ok(archive) => {
+                options.root = Some(archive.root.clone());
+                Some(archive)
             }
+            Err(e) => {
+                eprintln!("Error: could not read the /proc archive on stdin: {}", e);
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+    } else {
+        None
+    };
+
+    let status = match dispatch(&options) {
+        Ok(outcome) => outcome.exit_code(),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            EXIT_ERROR
         }
+    };
+    drop(archive);
+    drop(lock);
+    std::process::exit(status);
+}
+
+/// Runs the mode selected on the command line, under ptrace with
+/// --trace-self
+fn dispatch(options: &Options) -> io::Result<Outcome> {
+    if options.trace_self {
+        return trace_self(options);
     }
+    run_mode(options)
+}
 
-    #[cfg(not(target_os = "linux"))]
+/// Runs the mode selected on the command line
+fn run_mode(options: &Options) -> io::Result<Outcome> {
+    if options.dry_probe {
+        print_dry_probe(&host_info(options)?);
+        return Ok(Outcome::Clean);
+    }
+
+    if let Some(pid) = options.watch_pid {
+        let proc_root = options.proc_root();
+        let Some(name) = get_process_name(&proc_root, pid) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no process with PID {}", pid),
+            ));
+        };
+        return watch_pid(&proc_root, pid, &name).map(|()| Outcome::Clean);
+    }
+
+    if let Some(path) = &options.logfile {
+        return daemon(options, path).map(|()| Outcome::Clean);
+    }
+
+    if let Some(seconds) = options.watch {
+        return watch(options, seconds).map(|()| Outcome::Clean);
+    }
+
+    if options.quiet {
+        return print_quiet(options);
+    }
+
+    match options.format {
+        OutputFormat::Text => run(options),
+        OutputFormat::Influx => print_influx(options),
+        OutputFormat::Dot => print_dot(options),
+        OutputFormat::SyslogRfc5424 => print_syslog_rfc5424(options),
+        OutputFormat::Explain => print_explanation(options),
+        OutputFormat::Json => print_json(options),
+        OutputFormat::Prometheus => print_prometheus(options),
+        OutputFormat::Csv => print_csv(options),
+    }
+}
+
+/// Escapes a tag key, tag value or field key for the InfluxDB line protocol
+fn escape_influx_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Prints a single InfluxDB line protocol point summarizing the scan
+fn print_influx(options: &Options) -> io::Result<Outcome> {
+    let report = build_report(options, true)?;
+    let (sys_info, supported, detections) = (&report.system, report.supported, &report.processes);
+
+    let rings_total: usize = detections.iter().map(|d| d.rings.len()).sum();
+    let rss_total = total_memory(detections).resident_memory.unwrap_or(0);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or(0);
+
+    println!(
+        "io_uring,host={},supported={} processes_using={}i,rings_total={}i,rss_total={}i {}",
+        escape_influx_tag(&sys_info.hostname),
+        supported,
+        detections.len(),
+        rings_total,
+        rss_total,
+        timestamp
+    );
+
+    Ok(Outcome::of_scan(supported, detections))
+}
+
+/// Prints one Prometheus metric with its HELP and TYPE header
+fn print_prometheus_gauge(name: &str, help: &str, samples: &[(String, u64)]) {
+    println!("# HELP {} {}", name, help);
+    println!("# TYPE {} gauge", name);
+    for (labels, value) in samples {
+        println!("{}{} {}", name, labels, value);
+    }
+}
+
+/// Prints the scan as gauges in the Prometheus text exposition format,
+/// for node_exporter's textfile collector
+///
+/// Like --json, io_uring being unavailable is reported as a value of 0
+/// rather than as an error.
+fn print_prometheus(options: &Options) -> io::Result<Outcome> {
+    let report = build_report(options, true)?;
+    let detections = &report.processes;
+    let rings_total: usize = detections.iter().map(|d| d.rings.len()).sum();
+
+    print_prometheus_gauge(
+        "io_uring_supported",
+        "Whether io_uring_setup succeeded for the detector (1) or not (0).",
+        &[(String::new(), u64::from(report.supported))],
+    );
+    print_prometheus_gauge(
+        "io_uring_kernel_min_met",
+        &format!("Whether the kernel release is at least {}.{}.", options.min_kernel.0, options.min_kernel.1),
+        &[(String::new(), u64::from(report.system.min_kernel_version_met))],
+    );
+    print_prometheus_gauge(
+        "io_uring_feature_flags",
+        "io_uring feature flags reported by io_uring_setup.",
+        &report
+            .features
+            .iter()
+            .map(|name| (format!("{{name=\"{}\"}}", name), 1))
+            .collect::<Vec<_>>(),
+    );
+    print_prometheus_gauge(
+        "io_uring_processes_total",
+        "Number of processes holding at least one io_uring instance.",
+        &[(String::new(), detections.len() as u64)],
+    );
+    print_prometheus_gauge(
+        "io_uring_rings_total",
+        "Number of io_uring instances held by all processes.",
+        &[(String::new(), rings_total as u64)],
+    );
+
+    Ok(Outcome::of_scan(report.supported, detections))
+}
+
+/// Quotes a CSV field if it contains a comma, double quote or line break,
+/// doubling any quotes inside it (RFC 4180)
+fn escape_csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Prints a CSV header and one row per process using io_uring, for
+/// collecting results from many hosts into a single table
+///
+/// The header is printed even when nothing is found, so every host's
+/// output has the same columns. --fields picks and orders the columns.
+/// With --include-all-processes a uses_io_uring column is added, and every
+/// other scanned process follows with only its hostname, PID and name
+/// filled in.
+fn print_csv(options: &Options) -> io::Result<Outcome> {
+    let report = build_report(options, true)?;
+    let (sys_info, supported, detections) = (&report.system, report.supported, &report.processes);
+
+    let fields = options.fields.as_deref().unwrap_or(DEFAULT_CSV_FIELDS);
+    let all_processes = report.all_processes.as_deref();
+    let mut header: Vec<String> = fields.iter().map(|field| field.name()).collect();
+    if all_processes.is_some() {
+        header.push(String::from("uses_io_uring"));
+    }
+    println!("{}", header.join(","));
+    for detection in detections {
+        let mut row: Vec<String> = fields
+            .iter()
+            .map(|field| escape_csv_field(&csv_cell(&field.value(&sys_info.hostname, detection))))
+            .collect();
+        if all_processes.is_some() {
+            row.push(String::from("true"));
+        }
+        println!("{}", row.join(","));
+    }
+    for process in all_processes
+        .unwrap_or_default()
+        .iter()
+        .filter(|process| !detections.iter().any(|detection| detection.pid == process.pid))
     {
-        Ok(None) // io_uring is not supported on non-Linux systems
+        let mut row: Vec<String> = fields
+            .iter()
+            .map(|field| match field {
+                Field::Hostname => escape_csv_field(&sys_info.hostname),
+                Field::Pid => process.pid.to_string(),
+                Field::Name => escape_csv_field(process.name.as_deref().unwrap_or_default()),
+                _ => String::new(),
+            })
+            .collect();
+        row.push(process.uses_io_uring.map(|uses| uses.to_string()).unwrap_or_default());
+        println!("{}", row.join(","));
     }
+    // A count after the rows would break the table, so it goes to stderr
+    if let Some(suppressed) = report.suppressed_duplicates {
+        eprintln!("Suppressed {} duplicate process(es) of an executable already listed", suppressed);
+    }
+
+    Ok(Outcome::of_scan(supported, detections))
 }
 
-/// Prints the available io_uring features
-fn print_io_uring_features(params: &IoUringParams) {
-    println!("\nReported io_uring feature flags:");
-    let features = params.features;
-    let mut found_features = false;
+/// Explains an EPERM from io_uring_setup in terms of the detector's own
+/// privileges, since it does not by itself mean io_uring is unsupported
+fn print_setup_permission_denied(sys_info: &SystemInfo) {
+    println!("\nio_uring_setup exists but was denied with EPERM.");
+    match sys_info.io_uring_disabled {
+        Some(2) => {
+            println!("io_uring is supported by this kernel but disabled by policy:");
+            println!("kernel.io_uring_disabled=2 prevents every process from creating rings.");
+            return;
+        }
+        Some(1) => {
+            println!("io_uring is supported by this kernel but restricted by policy:");
+            println!("kernel.io_uring_disabled=1 limits ring creation to processes with");
+            println!("CAP_SYS_ADMIN or in the kernel.io_uring_group group; run the");
+            println!("detector as root to probe it.");
+            return;
+        }
+        _ => {}
+    }
+    if sys_info.reason_code == SupportReason::BlockedSeccomp {
+        println!("io_uring is blocked by policy: the detector runs under a seccomp");
+        println!("filter that rejects io_uring_setup. Processes outside the filter may");
+        println!("still be able to use io_uring.");
+        return;
+    }
+    match has_cap_sys_admin() {
+        Some(true) => {
+            println!("The detector already has CAP_SYS_ADMIN, so io_uring is unavailable");
+            println!("regardless of privileges (e.g. disabled by sysctl, seccomp or an LSM).");
+        }
+        Some(false) => {
+            println!("The detector lacks CAP_SYS_ADMIN. This kernel may restrict io_uring to");
+            println!("privileged processes, so running as root or with CAP_SYS_ADMIN would");
+            println!("likely change the result; io_uring is unavailable to this process, not");
+            println!("necessarily unsupported.");
+        }
+        None => {
+            println!("Could not read CapEff to tell whether more privileges would help.");
+        }
+    }
+}
+
+/// Explains an ENOMEM, EMFILE or ENFILE from io_uring_setup, which means the
+/// syscall exists but the detector could not allocate its probe ring, and
+/// lists the other signs that the kernel has io_uring
+fn print_resource_limited(sys_info: &SystemInfo, error: &io::Error) {
+    println!("\nio_uring_setup exists but failed: {}", error);
+    println!("io_uring is likely supported but the detector is resource-constrained:");
+    if error.raw_os_error() == Some(libc::ENOMEM) {
+        println!("the probe ring could not be allocated, usually because of a low");
+        println!("RLIMIT_MEMLOCK (ulimit -l) on kernels before 5.12.");
+    } else {
+        println!("the detector has run out of file descriptors (ulimit -n or fs.file-max).");
+    }
+
+    println!("Corroborating signals:");
+    match find_io_uring_setup_symbol() {
+        Ok(Some(symbol)) => println!("  Kernel symbol: {} (present in /proc/kallsyms)", symbol),
+        Ok(None) => println!("  Kernel symbol: io_uring_setup not found in /proc/kallsyms"),
+        Err(e) => println!("  Kernel symbol: /proc/kallsyms unavailable ({})", e),
+    }
+    match sys_info.io_uring_disabled {
+        Some(value) => println!("  kernel.io_uring_disabled: present ({})", value),
+        None => println!("  kernel.io_uring_disabled: absent (kernels before 6.6 lack it)"),
+    }
+    match sys_info.kernel_config.as_ref().map(KernelConfig::io_uring_enabled) {
+        Some(true) => println!("  Kernel config: CONFIG_IO_URING=y"),
+        Some(false) => println!("  Kernel config: CONFIG_IO_URING is not enabled"),
+        None => println!("  Kernel config: unavailable"),
+    }
+    println!("Reason code: {:?}", sys_info.reason_code);
+}
+
+/// Escapes a string for use inside a double-quoted DOT ID
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Prints a Graphviz DOT graph linking each process to the rings it holds
+///
+/// Rings are identified by inode so that a ring shared through fork or fd
+/// passing becomes one node with several processes attached; rings held by
+/// unrelated processes are highlighted. Before kernel 5.12 all rings share
+/// an inode, so every fd gets its own ring node instead.
+fn print_dot(options: &Options) -> io::Result<Outcome> {
+    let report = build_report(options, true)?;
+    let (sys_info, supported, detections) = (&report.system, report.supported, &report.processes);
+
+    println!("graph io_uring {{");
+    println!("  node [shape=box];");
+    for detection in detections {
+        println!(
+            "  \"pid_{}\" [label=\"{}\\nPID {}\"];",
+            detection.pid,
+            escape_dot(&detection.info.name),
+            detection.pid
+        );
+    }
 
-    for (mask, name) in IO_URING_FEATURES {
-        if (features & mask) != 0 {
-            println!("  - {}", name);
-            found_features = true;
+    if ring_inodes_unique(sys_info) {
+        for (inode, holders) in group_rings_by_inode(detections) {
+            let style = if holders_unrelated(&options.proc_root(), &holders) {
+                ", color=red, xlabel=\"possibly fd-passed\""
+            } else {
+                ""
+            };
+            println!("  \"ring_{}\" [shape=ellipse, label=\"ring\\ninode {}\"{}];", inode, inode, style);
+            for (detection, fd) in holders {
+                println!("  \"pid_{}\" -- \"ring_{}\" [label=\"fd {}\"];", detection.pid, inode, fd);
+            }
+        }
+    } else {
+        for detection in detections {
+            for ring in &detection.rings {
+                println!(
+                    "  \"ring_{}_{}\" [shape=ellipse, label=\"ring\"];",
+                    detection.pid, ring.fd
+                );
+                println!(
+                    "  \"pid_{}\" -- \"ring_{}_{}\" [label=\"fd {}\"];",
+                    detection.pid, detection.pid, ring.fd, ring.fd
+                );
+            }
         }
     }
+    println!("}}");
 
-    if !found_features {
-        println!("  (no features reported)");
+    Ok(Outcome::of_scan(supported, detections))
+}
+
+/// Escapes an RFC 5424 SD-PARAM value
+fn escape_sd_param(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
     }
+    escaped
 }
 
-/// Gets the process name for a given PID
-fn get_process_name(pid: u32) -> Option<String> {
-    let path = format!("/proc/{}/comm", pid);
-    read_to_string(path).ok().map(|s| s.trim().to_string())
+/// Syslog facility used for detection messages (daemon)
+const SYSLOG_FACILITY: u8 = 3;
+/// Severity for high-risk detections (warning)
+const SYSLOG_SEVERITY_WARNING: u8 = 4;
+/// Severity for other detections (notice)
+const SYSLOG_SEVERITY_NOTICE: u8 = 5;
+/// SD-ID of the structured data element; 32473 is the private enterprise
+/// number reserved for documentation (RFC 5612)
+const SYSLOG_SD_ID: &str = "iouring@32473";
+
+/// Prints one RFC 5424 syslog message per detection, with the process
+/// details carried as structured data
+fn print_syslog_rfc5424(options: &Options) -> io::Result<Outcome> {
+    let report = build_report(options, true)?;
+    let (sys_info, supported, detections) = (&report.system, report.supported, &report.processes);
+
+    let timestamp = format_rfc3339(SystemTime::now());
+    // HOSTNAME is limited to printable US-ASCII without spaces
+    let hostname: String = sys_info
+        .hostname
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .collect();
+    let hostname = if hostname.is_empty() { String::from("-") } else { hostname };
+
+    for detection in detections {
+        let severity = if is_high_risk(detection) {
+            SYSLOG_SEVERITY_WARNING
+        } else {
+            SYSLOG_SEVERITY_NOTICE
+        };
+        let mut params = format!(
+            "pid=\"{}\" name=\"{}\"",
+            detection.pid,
+            escape_sd_param(&detection.info.name)
+        );
+        if let Some(uid) = detection.info.uid {
+            params.push_str(&format!(" uid=\"{}\"", uid));
+        }
+        params.push_str(&format!(" ring_count=\"{}\"", detection.rings.len()));
+
+        println!(
+            "<{}>1 {} {} io_uring_detector {} detection [{} {}] Process {} ({}) is using io_uring",
+            SYSLOG_FACILITY * 8 + severity,
+            timestamp,
+            hostname,
+            std::process::id(),
+            SYSLOG_SD_ID,
+            params,
+            detection.pid,
+            detection.info.name
+        );
+    }
+
+    Ok(Outcome::of_scan(supported, detections))
 }
 
-/// Gets detailed process information including command line arguments and memory status
-fn get_process_info(pid: u32) -> ProcessInfo {
-    let mut info = ProcessInfo {
-        name: get_process_name(pid).unwrap_or_else(|| "<unknown>".to_string()),
-        exe_path: None,
-        cmdline: None,
-        memory_status: None,
-        is_in_memory: false,
-    };
+/// Prints how this binary was built and what it will call on its target
+fn print_build_info() {
+    println!("Build information:");
+    println!("  Version: {}", env!("CARGO_PKG_VERSION"));
+    println!("  Target: {}", env!("BUILD_TARGET"));
+    println!("  Profile: {}", env!("BUILD_PROFILE"));
+    println!(
+        "  Statically linked C runtime: {}",
+        if cfg!(target_feature = "crt-static") { "yes" } else { "no" }
+    );
+    // The detector issues raw syscalls through libc, so the only optional
+    // features are its own
+    let features: Vec<&str> = [("bpf", cfg!(feature = "bpf")), ("ffi", cfg!(feature = "ffi"))]
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect();
+    println!("  Crate features: {}", if features.is_empty() { String::from("none") } else { features.join(", ") });
+
+    #[cfg(target_os = "linux")]
+    {
+        #[allow(clippy::unnecessary_cast)]
+        let nr = SYS_io_uring_setup as i64;
+        println!("  SYS_io_uring_setup: {}", nr);
+    }
 
-    // Get executable path
-    if let Ok(path) = read_link(format!("/proc/{}/exe", pid)) {
-        info.exe_path = Some(path);
+    #[cfg(not(target_os = "linux"))]
+    {
+        println!("  SYS_io_uring_setup: not available on this platform");
     }
+}
+
+/// The --json document: the report, with per-user totals for --count-by-user
+#[derive(Debug, Serialize)]
+struct JsonReport<'a> {
+    #[serde(flatten)]
+    report: &'a Report,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage_by_user: Option<Vec<UserUsage>>,
+}
 
-    // Get command line arguments
-    if let Ok(cmdline) = read_to_string(format!("/proc/{}/cmdline", pid)) {
-        let args: Vec<String> = cmdline
-            .split('\0')
-            .filter(|s| !s.is_empty())
-            .map(String::from)
+/// Prints the system information, features and detections as one JSON
+/// document, or writes it into --output-dir
+///
+/// With --fields each detection becomes a flat object holding only the
+/// selected fields, and with --batch-size the document is split by
+/// [`print_json_batches`].
+fn print_json(options: &Options) -> io::Result<Outcome> {
+    let report = build_report(options, true)?;
+    let document = JsonReport {
+        report: &report,
+        usage_by_user: options.count_by_user.then(|| usage_by_user(&report.processes)),
+    };
+    let mut document = serde_json::to_value(&document)?;
+    if let Some(fields) = &options.fields {
+        document["processes"] = report
+            .processes
+            .iter()
+            .map(|detection| {
+                fields
+                    .iter()
+                    .map(|field| (field.name(), field.value(&report.system.hostname, detection)))
+                    .collect::<serde_json::Map<_, _>>()
+            })
             .collect();
-        if !args.is_empty() {
-            info.cmdline = Some(args);
-        }
-    }
-
-    // Check if process is in memory
-    if let Ok(maps) = read_to_string(format!("/proc/{}/maps", pid)) {
-        // Check for memory-mapped files
-        let has_memory_mapped_files = maps.lines().any(|line| {
-            line.contains("memfd:") || 
-            line.contains("anon_inode:") ||
-            line.contains("(deleted)")
-        });
-        info.is_in_memory = has_memory_mapped_files;
-
-        // Get memory status
-        if let Ok(status) = read_to_string(format!("/proc/{}/status", pid)) {
-            let mut memory_info = MemoryInfo::default();
-            
-            for line in status.lines() {
-                if line.starts_with("VmSize:") {
-                    if let Some(size) = line.split_whitespace().nth(1) {
-                        memory_info.virtual_memory = size.parse().ok();
-                    }
-                } else if line.starts_with("VmRSS:") {
-                    if let Some(size) = line.split_whitespace().nth(1) {
-                        memory_info.resident_memory = size.parse().ok();
-                    }
-                }
+    }
+    match &options.output_dir {
+        Some(dir) => {
+            let path = write_output_file(dir, &report.system.hostname, &document)?;
+            info!("wrote {}", path.display());
+        }
+        None => match options.batch_size {
+            Some(size) => print_json_batches(document, usize::try_from(size).unwrap_or(usize::MAX))?,
+            None => {
+                serde_json::to_writer_pretty(io::stdout().lock(), &document)?;
+                println!();
             }
-            info.memory_status = Some(memory_info);
+        },
+    }
+
+    Ok(Outcome::of_scan(report.supported, &report.processes))
+}
+
+/// Prints the --json document as a series of single-line documents, each
+/// holding at most `size` entries of `processes` and of `all_processes`
+///
+/// Every document repeats the other fields, so each can be handled on its
+/// own, and adds `batch` with its 1-based `index` and the `count` of
+/// documents. Each is flushed as soon as it is written.
+fn print_json_batches(mut document: serde_json::Value, size: usize) -> io::Result<()> {
+    use std::io::Write;
+
+    // Taking leaves null in place, so the keys keep their position
+    let mut take = |key: &str| match document.get_mut(key).map(serde_json::Value::take) {
+        Some(serde_json::Value::Array(items)) => Some(items),
+        _ => None,
+    };
+    let processes = take("processes").unwrap_or_default();
+    let all_processes = take("all_processes");
+    let count = processes
+        .len()
+        .max(all_processes.as_ref().map_or(0, Vec::len))
+        .div_ceil(size)
+        .max(1);
+
+    let mut stdout = io::stdout().lock();
+    let mut process_batches = processes.chunks(size);
+    let mut all_batches = all_processes.as_deref().map(|items| items.chunks(size));
+    for index in 1..=count {
+        document["processes"] = process_batches.next().unwrap_or_default().into();
+        if let Some(batches) = &mut all_batches {
+            document["all_processes"] = batches.next().unwrap_or_default().into();
         }
+        document["batch"] = serde_json::json!({ "index": index, "count": count });
+        serde_json::to_writer(&mut stdout, &document)?;
+        writeln!(stdout)?;
+        stdout.flush()?;
     }
+    Ok(())
+}
+
+/// Writes a JSON document to DIR/<hostname>-<timestamp>.json, creating DIR
+/// if needed, and returns the path written
+///
+/// The timestamp is UTC in basic ISO 8601 form, e.g. 20240101T120000Z, so
+/// names sort by time and contain no colons. The file is written under a
+/// temporary name and renamed, so a collector never sees a partial report.
+fn write_output_file(dir: &Path, hostname: &str, document: &serde_json::Value) -> io::Result<PathBuf> {
+    let stamp: String = format_rfc3339(SystemTime::now())
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !matches!(c, '-' | ':'))
+        .collect();
+    let hostname = if hostname.is_empty() { "unknown" } else { hostname };
+    let path = dir.join(format!("{}-{}Z.json", hostname, stamp));
 
-    info
+    std::fs::create_dir_all(dir)
+        .map_err(|e| io::Error::new(e.kind(), format!("could not create {}: {}", dir.display(), e)))?;
+    let mut contents = serde_json::to_vec_pretty(document)?;
+    contents.push(b'\n');
+    let temp_path = dir.join(format!(".{}-{}Z.json.tmp", hostname, stamp));
+    std::fs::write(&temp_path, contents)
+        .and_then(|()| std::fs::rename(&temp_path, &path))
+        .map_err(|e| io::Error::new(e.kind(), format!("could not write {}: {}", path.display(), e)))?;
+    Ok(path)
 }
 
-/// Structure to hold process information
-#[derive(Debug, Default)]
-struct ProcessInfo {
-    name: String,
-    exe_path: Option<PathBuf>,
-    cmdline: Option<Vec<String>>,
-    memory_status: Option<MemoryInfo>,
-    is_in_memory: bool,
+/// Picks the singular or plural form of a noun for a count
+fn plural(count: usize, singular: &str, plural: &str) -> String {
+    format!("{} {}", count, if count == 1 { singular } else { plural })
 }
 
-/// Structure to hold memory information
-#[derive(Debug, Default)]
-struct MemoryInfo {
-    virtual_memory: Option<u64>,
-    resident_memory: Option<u64>,
+/// Describes one detected process in a sentence or two
+fn explain_detection(detection: &Detection) -> String {
+    let info = &detection.info;
+    let owner = info
+        .username
+        .clone()
+        .or_else(|| info.uid.map(|uid| format!("UID {}", uid)));
+    let mut text = match owner {
+        Some(owner) => format!("{} (PID {}, owned by {})", info.name, detection.pid, owner),
+        None => format!("{} (PID {})", info.name, detection.pid),
+    };
+    text.push_str(&format!(" has {}", plural(detection.rings.len(), "ring", "rings")));
+    if !detection.mapped_ring_inodes.is_empty() {
+        text.push_str(&format!(
+            " open and {} mapped without an fd",
+            plural(detection.mapped_ring_inodes.len(), "ring", "rings")
+        ));
+    }
+
+    let sqpoll = detection.rings.iter().filter(|ring| ring.uses_sqpoll()).count();
+    if sqpoll > 0 {
+        text.push_str(&format!(
+            ", {} a kernel thread to poll for submissions",
+            if sqpoll == 1 { "one of which uses" } else { "several of which use" }
+        ));
+    }
+    let workloads: Vec<&str> = detection
+        .rings
+        .iter()
+        .filter_map(|ring| ring.inferred_workload.as_deref())
+        .collect();
+    if let Some(first) = workloads.first() {
+        if workloads.iter().all(|workload| workload == first) {
+            text.push_str(&format!(", and appears to do {} I/O", first));
+        }
+    }
+    text.push('.');
+
+    let devices: Vec<String> = detection
+        .rings
+        .iter()
+        .flat_map(|ring| &ring.backing_devices)
+        .map(|device| device.display().to_string())
+        .collect();
+    if !devices.is_empty() {
+        text.push_str(&format!(
+            " It has direct access to the storage device {}.",
+            devices.join(", ")
+        ));
+    }
+    if runs_fileless(info) {
+        // A memfd's exe link also ends in " (deleted)", so it is told
+        // apart by its prefix
+        let exe = info.exe_path.as_ref().map(|path| path.to_string_lossy());
+        let origin = match exe.as_deref() {
+            Some(exe) if exe.starts_with("/memfd:") => "It runs from a memfd, an in-memory file that was never on disk,",
+            Some(_) => "Its executable no longer exists on disk,",
+            None => "It runs from memory (a memfd or a deleted file),",
+        };
+        text.push_str(&format!(" {} which is unusual and worth a closer look.", origin));
+    }
+    if info.state == 'D' {
+        text.push_str(" It is currently stuck waiting on I/O.");
+    }
+    if let Some(rss) = info.memory_status.as_ref().and_then(|memory| memory.resident_memory) {
+        text.push_str(&format!(" It uses {} MB of memory.", rss / 1024));
+    }
+    text
 }
 
-/// Checks if any running processes are using io_uring
-fn check_io_uring_usage() -> io::Result<()> {
-    println!("\nChecking if any process is using io_uring...");
+/// Prints the scan results as a short narrative
+fn print_explanation(options: &Options) -> io::Result<Outcome> {
+    let report = build_report(options, true)?;
+    let sys_info = &report.system;
+    let kernel = parse_kernel_version(&sys_info.kernel_version)
+        .map(|(major, minor)| format!("{}.{}", major, minor))
+        .unwrap_or_else(|| sys_info.kernel_version.clone());
 
-    let mut found = false;
-    let proc_entries = read_dir("/proc")?;
-
-    for entry in proc_entries.flatten() {
-        if let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() {
-            let fd_dir = format!("/proc/{}/fd", pid);
-            if let Ok(fds) = read_dir(fd_dir) {
-                for fd_entry in fds.flatten() {
-                    if let Ok(link_target) = read_link(fd_entry.path()) {
-                        if link_target.to_string_lossy().contains("anon_inode:[io_uring]") {
-                            let info = get_process_info(pid);
-                            
-                            println!("\nProcess using io_uring:");
-                            println!("  PID: {}", pid);
-                            println!("  Name: {}", info.name);
-                            
-                            if let Some(path) = info.exe_path {
-                                println!("  Executable: {}", path.display());
-                            } else {
-                                println!("  Executable: <unavailable>");
-                            }
-
-                            if let Some(cmdline) = info.cmdline {
-                                println!("  Command line: {}", cmdline.join(" "));
-                            }
-
-                            if info.is_in_memory {
-                                println!("  Status: Running in memory");
-                            }
-
-                            if let Some(memory) = info.memory_status {
-                                if let Some(vm) = memory.virtual_memory {
-                                    println!("  Virtual Memory: {} kB", vm);
-                                }
-                                if let Some(rss) = memory.resident_memory {
-                                    println!("  Resident Memory: {} kB", rss);
-                                }
-                            }
-
-                            println!("  io_uring FD: {:?}", fd_entry.file_name());
-                            found = true;
-                            break;
-                        }
-                    }
-                }
-            }
+    let verdict = match sys_info.reason_code {
+        SupportReason::Ok => String::from("which supports io_uring"),
+        SupportReason::KernelTooOld => String::from("which is too old to support io_uring (5.1 or newer is needed)"),
+        SupportReason::Enosys => String::from("which was built without io_uring"),
+        SupportReason::DisabledSysctl => String::from("where io_uring has been switched off by the administrator"),
+        SupportReason::BlockedSeccomp => String::from("where io_uring is blocked by a seccomp filter"),
+        SupportReason::BlockedLsm => String::from("where io_uring is blocked by a security policy"),
+        SupportReason::NoPermission => String::from("where io_uring is restricted to privileged users"),
+        SupportReason::ResourceLimited => {
+            String::from("which likely supports io_uring, though the detector was out of memory or fds to try it")
         }
+        SupportReason::UnknownError => String::from("where it could not be determined whether io_uring works"),
+    };
+    println!(
+        "This host ({}) runs Linux kernel {} on {}, {}.",
+        sys_info.hostname, kernel, sys_info.architecture, verdict
+    );
+    if sys_info.cgroup_io_limited == Some(true) {
+        println!("The detector's cgroup has I/O limits, so io_uring I/O may be throttled.");
+    }
+    if !report.supported {
+        return Ok(Outcome::Clean);
     }
 
-    if !found {
-        println!("No processes using io_uring were found.");
+    let detections = &report.processes;
+    if detections.is_empty() {
+        println!("No processes are currently using it.");
+        return Ok(Outcome::Unused);
     }
 
-    Ok(())
+    println!(
+        "{} currently using it:",
+        if detections.len() == 1 {
+            String::from("1 process is")
+        } else {
+            format!("{} processes are", detections.len())
+        }
+    );
+    for detection in detections {
+        println!("  - {}", explain_detection(detection));
+    }
+
+    if ring_inodes_unique(sys_info) {
+        let passed = group_rings_by_inode(detections)
+            .iter()
+            .filter(|(_, holders)| holders_unrelated(&options.proc_root(), holders))
+            .count();
+        if passed > 0 {
+            println!(
+                "{} shared between unrelated processes, which suggests ring handles were passed between them.",
+                if passed == 1 { String::from("1 ring is") } else { format!("{} rings are", passed) }
+            );
+        }
+    }
+
+    let rss = total_memory(detections).resident_memory.unwrap_or(0);
+    let rings: usize = detections.iter().map(|detection| detection.rings.len()).sum();
+    println!(
+        "In total they hold {} and use {} MB of memory.",
+        plural(rings, "ring", "rings"),
+        rss / 1024
+    );
+
+    Ok(Outcome::InUse)
+}
+
+/// Prints the host details gathered by system_info
+fn print_system_info(sys_info: &SystemInfo) {
+    println!("\nSystem Information:");
+    println!("  Architecture: {}", sys_info.architecture);
+    println!("  Kernel Version: {}", sys_info.kernel_version);
+    if let Some(proc_version) = &sys_info.proc_version {
+        println!("  /proc/version: {}", proc_version);
+    }
+    if sys_info.kernel_version_mismatch() {
+        println!("  Warning: uname and /proc/version disagree on the kernel version; uname may be spoofed and the minimum version check may be wrong");
+    }
+    println!("  Node Name: {}", sys_info.hostname);
+    if let Some(machine_id) = &sys_info.machine_id {
+        println!("  Machine ID: {}", machine_id);
+    }
+
+    match sys_info.cgroup_io_limited {
+        Some(true) => println!("  Cgroup I/O Limits: in effect (io.max); io_uring I/O may be throttled"),
+        Some(false) => println!("  Cgroup I/O Limits: none"),
+        None => {}
+    }
+
+    match sys_info.io_uring_disabled {
+        Some(0) => println!("  kernel.io_uring_disabled: 0 (unrestricted)"),
+        Some(1) => println!("  kernel.io_uring_disabled: 1 (restricted to privileged processes)"),
+        Some(2) => println!("  kernel.io_uring_disabled: 2 (disabled)"),
+        Some(value) => println!("  kernel.io_uring_disabled: {}", value),
+        None => {}
+    }
+
+    if let Some(config) = &sys_info.kernel_config {
+        println!("  Kernel Config: {}", config.source.display());
+        for name in IO_URING_CONFIG_OPTIONS {
+            println!("    {}={}", name, config.value(name).unwrap_or("<absent>"));
+        }
+    }
+}
+
+/// Explains why io_uring_setup is not implemented on this system
+fn print_unsupported(sys_info: &SystemInfo) {
+    let config_enabled = sys_info
+        .kernel_config
+        .as_ref()
+        .map(KernelConfig::io_uring_enabled);
+
+    if config_enabled == Some(false) {
+        println!("\nio_uring is not supported on this Linux system.");
+        println!("The kernel config reports CONFIG_IO_URING is not enabled.");
+    } else if config_enabled == Some(true) {
+        println!("\nio_uring_setup is unavailable on this Linux system.");
+        println!("The kernel config reports CONFIG_IO_URING=y, so the syscall");
+        println!("is most likely being filtered (e.g. by seccomp).");
+    } else if cfg!(target_os = "linux") {
+        println!("\nio_uring is not supported on this Linux system.");
+        println!("This could be due to:");
+        println!("  - Kernel version being too old (requires 5.1+)");
+        println!("  - io_uring module not being loaded");
+        println!("  - Hardware or distribution limitations");
+    } else {
+        println!("\nio_uring is not supported on this non-Linux system.");
+    }
+    println!("Reason code: {:?}", sys_info.reason_code);
 }
 
-fn main() -> io::Result<()> {
-    println!("IO_Uring Detector");
-    println!("----------------");
+/// Prints the bare results for --quiet: the supported feature names with
+/// --features-only, otherwise the PID of each process using io_uring
+fn print_quiet(options: &Options) -> io::Result<Outcome> {
+    let report = build_report(options, !options.features_only)?;
+    if !report.supported {
+        return Ok(Outcome::Clean);
+    }
+
+    if options.features_only {
+        for name in &report.features {
+            println!("{}", name);
+        }
+        return Ok(Outcome::Clean);
+    }
+
+    for detection in &report.processes {
+        println!("{}", detection.pid);
+    }
+    Ok(Outcome::of_scan(true, &report.processes))
+}
+
+/// Prints the text report; --features-only stops before the process scan
+/// and --processes-only leaves out the system and feature sections
+fn run(options: &Options) -> io::Result<Outcome> {
+    if !options.processes_only {
+        println!("IO_Uring Detector");
+        println!("----------------");
+    }
+
+    let (report, trend) = build_report_with_trend(options, !options.features_only)?;
+    let sys_info = &report.system;
 
-    // Get system information
-    match get_system_info() {
-        Ok(mut sys_info) => {
-            println!("\nSystem Information:");
-            println!("  Architecture: {}", sys_info.architecture);
-            println!("  Kernel Version: {}", sys_info.kernel_version);
-            
-            if !sys_info.min_kernel_version_met {
+    if !options.processes_only {
+        print_system_info(sys_info);
+
+        if !sys_info.min_kernel_version_met {
+            if options.min_kernel == MIN_KERNEL_VERSION {
                 println!("\nWarning: Kernel version is below 5.1, which is required for io_uring support");
+            } else {
+                println!(
+                    "\nWarning: Kernel version is below {}.{}, the minimum given with --min-kernel",
+                    options.min_kernel.0, options.min_kernel.1
+                );
             }
+        }
+    }
 
-            match detect_io_uring_support()? {
-                Some(params) => {
-                    println!("\nio_uring is supported on this system!");
-                    sys_info.io_uring_support = true;
-                    print_io_uring_features(&params);
-                    check_io_uring_usage()?;
-                }
-                None => {
-                    if cfg!(target_os = "linux") {
-                        println!("\nio_uring is not supported on this Linux system.");
-                        println!("This could be due to:");
-                        println!("  - Kernel version being too old (requires 5.1+)");
-                        println!("  - io_uring module not being loaded");
-                        println!("  - Hardware or distribution limitations");
-                    } else {
-                        println!("\nio_uring is not supported on this non-Linux system.");
-                    }
-                }
-            }
+    let params = match &report.probe {
+        UringStatus::Supported(params) => params,
+        UringStatus::NotImplemented => {
+            print_unsupported(sys_info);
+            print_run_summary(sys_info, &[]);
+            return Ok(Outcome::Clean);
         }
-        Err(e) => {
-            println!("\nError getting system information: {}", e);
+        UringStatus::Blocked => {
+            print_setup_permission_denied(sys_info);
+            println!("Reason code: {:?}", sys_info.reason_code);
+            print_run_summary(sys_info, &[]);
+            return Ok(Outcome::Clean);
+        }
+        UringStatus::ResourceLimited(e) => {
+            print_resource_limited(sys_info, e);
+            print_run_summary(sys_info, &[]);
+            return Ok(Outcome::Clean);
+        }
+        // build_report fails the run on unexpected probe errors
+        UringStatus::Error(_) => unreachable!("io_uring_setup errors fail the report"),
+    };
+
+    if !options.processes_only {
+        println!("\n{}", paint("io_uring is supported on this system!", GREEN));
+        print_io_uring_features(params);
+        print_restrictions_support();
+        print_supported_ops();
+        if options.dump_params {
+            print_io_uring_params(params);
         }
     }
 
-    Ok(())
-} 
\ No newline at end of file
+    if options.features_only {
+        print_run_summary(sys_info, &[]);
+        return Ok(Outcome::Clean);
+    }
+
+    check_io_uring_usage(options, &report);
+    let detections = &report.processes;
+
+    report_passed_rings(&options.proc_root(), sys_info, detections);
+    print_io_uring_threads(options);
+
+    if options.count_by_user && !detections.is_empty() {
+        print_usage_by_user(detections);
+    }
+
+    if let (Some(path), Some(trend)) = (&options.state_file, &trend) {
+        print_trend(path, trend);
+    }
+
+    print_run_summary(sys_info, detections);
+    Ok(Outcome::of_scan(true, detections))
+}
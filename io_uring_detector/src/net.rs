@@ -0,0 +1,192 @@
+//! Resolving socket fds to network connections through /proc/<pid>/net
+
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::RawFd;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::process::read_proc_file;
+
+/// Socket tables under /proc/<pid>/net, with the protocol they list
+const SOCKET_TABLES: &[(&str, &str)] = &[("tcp", "tcp"), ("tcp6", "tcp"), ("udp", "udp"), ("udp6", "udp")];
+
+/// TCP states by number, as defined in include/net/tcp_states.h
+const TCP_STATES: &[&str] = &[
+    "UNKNOWN",
+    "ESTABLISHED",
+    "SYN_SENT",
+    "SYN_RECV",
+    "FIN_WAIT1",
+    "FIN_WAIT2",
+    "TIME_WAIT",
+    "CLOSE",
+    "CLOSE_WAIT",
+    "LAST_ACK",
+    "LISTEN",
+    "CLOSING",
+    "NEW_SYN_RECV",
+];
+
+/// A TCP or UDP socket held by a process, from its network namespace's
+/// socket tables
+#[derive(Debug, Clone, Serialize)]
+pub struct Connection {
+    /// "tcp" or "udp"
+    pub protocol: &'static str,
+    pub local: SocketAddr,
+    /// Unspecified address and port 0 for listening and unconnected sockets
+    pub remote: SocketAddr,
+    /// TCP state name, None for UDP
+    pub state: Option<&'static str>,
+    /// Inode of the socket, as in the fd's `socket:[inode]` target
+    pub inode: u64,
+}
+
+impl Connection {
+    /// Whether the socket has a peer, i.e. is neither listening nor unconnected
+    pub fn has_remote(&self) -> bool {
+        !self.remote.ip().is_unspecified() || self.remote.port() != 0
+    }
+}
+
+/// Extracts the inode from a `socket:[inode]` fd link target
+pub(crate) fn socket_inode(target: &str) -> Option<u64> {
+    target.strip_prefix("socket:[")?.strip_suffix(']')?.parse().ok()
+}
+
+/// Parses an `ADDR:PORT` socket table address
+///
+/// The address is printed as 32-bit words in host byte order, one word for
+/// IPv4 and four for IPv6; the port is big-endian hex.
+fn parse_address(field: &str) -> Option<SocketAddr> {
+    let (address, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    let mut bytes = Vec::with_capacity(16);
+    for start in (0..address.len()).step_by(8) {
+        let word = u32::from_str_radix(address.get(start..start + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match bytes.len() {
+        4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// Parses the contents of a /proc/net/{tcp,tcp6,udp,udp6} table
+fn parse_socket_table(contents: &str, protocol: &'static str) -> Vec<Connection> {
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let state = u8::from_str_radix(fields.get(3)?, 16).ok()?;
+            Some(Connection {
+                protocol,
+                local: parse_address(fields.get(1)?)?,
+                remote: parse_address(fields.get(2)?)?,
+                state: (protocol == "tcp").then(|| TCP_STATES.get(state as usize).copied().unwrap_or("UNKNOWN")),
+                inode: fields.get(9)?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Resolves a process's socket fds to TCP and UDP connections
+///
+/// The tables are read from /proc/<pid>/net so that they cover the
+/// process's own network namespace. Unix and other sockets are left out.
+pub(crate) fn get_connections(proc_root: &Path, pid: u32, open_fds: &[(RawFd, String)]) -> Vec<Connection> {
+    let inodes: HashSet<u64> = open_fds.iter().filter_map(|(_, target)| socket_inode(target)).collect();
+    if inodes.is_empty() {
+        return Vec::new();
+    }
+
+    let mut connections = Vec::new();
+    for (table, protocol) in SOCKET_TABLES {
+        let Ok(contents) = read_proc_file(proc_root.join(format!("{}/net/{}", pid, table))) else {
+            continue;
+        };
+        connections.extend(
+            parse_socket_table(&contents, protocol)
+                .into_iter()
+                .filter(|connection| inodes.contains(&connection.inode)),
+        );
+    }
+    connections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Addresses are printed in host byte order; these lines are from x86_64
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0F02000A:0016 0202000A:D5A4 01 00000000:00000000 02:000A3F1C 00000000     0        0 24387 4 0000000000000000 20 4 31 10 -1
+   1: garbage
+   2: 0100007F:ZZZZ 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 968 1 0000000000000000 100 0 0 10 0
+";
+    const TCP6: &str = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000001000000:0277 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 19381 1 0000000000000000 100 0 0 10 0
+";
+    const UDP: &str = "   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  123: 3500007F:0035 00000000:0000 07 00000000:00000000 00:00000000 00000000   101        0 17500 2 0000000000000000 0
+";
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn tcp_established() {
+        let connections = parse_socket_table(TCP, "tcp");
+        // The malformed line and the one with a bad port are skipped
+        assert_eq!(connections.len(), 1);
+        let connection = &connections[0];
+        assert_eq!(connection.local, "10.0.2.15:22".parse().unwrap());
+        assert_eq!(connection.remote, "10.0.2.2:54692".parse().unwrap());
+        assert_eq!(connection.state, Some("ESTABLISHED"));
+        assert_eq!(connection.inode, 24387);
+        assert!(connection.has_remote());
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn tcp6_listen() {
+        let connections = parse_socket_table(TCP6, "tcp");
+        assert_eq!(connections.len(), 1);
+        let connection = &connections[0];
+        assert_eq!(connection.local, "[::1]:631".parse().unwrap());
+        assert_eq!(connection.state, Some("LISTEN"));
+        assert_eq!(connection.inode, 19381);
+        assert!(!connection.has_remote());
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn udp_has_no_state() {
+        let connections = parse_socket_table(UDP, "udp");
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].local, "127.0.0.53:53".parse().unwrap());
+        assert_eq!(connections[0].state, None);
+        assert_eq!(connections[0].inode, 17500);
+    }
+
+    #[test]
+    fn malformed_addresses() {
+        assert_eq!(parse_address("0100007F"), None);
+        assert_eq!(parse_address("0100007F:XYZ"), None);
+        assert_eq!(parse_address("0100:0016"), None);
+        assert_eq!(parse_address("0100007F0100007F:0016"), None);
+    }
+
+    #[test]
+    fn socket_inodes() {
+        assert_eq!(socket_inode("socket:[24387]"), Some(24387));
+        assert_eq!(socket_inode("socket:[]"), None);
+        assert_eq!(socket_inode("socket:[12"), None);
+        assert_eq!(socket_inode("pipe:[24387]"), None);
+        assert_eq!(socket_inode("anon_inode:[io_uring]"), None);
+    }
+}
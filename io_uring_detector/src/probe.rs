@@ -0,0 +1,488 @@
+//! Probing the kernel for io_uring support with io_uring_setup
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::os::fd::RawFd;
+
+#[cfg(target_os = "linux")]
+use libc::{c_uint, syscall, SYS_io_uring_register, SYS_io_uring_setup};
+
+/// Structure representing io_uring parameters
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct IoUringParams {
+    pub sq_entries: u32,
+    pub cq_entries: u32,
+    pub flags: u32,
+    pub sq_thread_cpu: u32,
+    pub sq_thread_idle: u32,
+    pub features: u32,
+    pub wq_fd: u32,
+    pub resv: [u32; 3],
+    pub sq_off: IoSqringOffsets,
+    pub cq_off: IoCqringOffsets,
+}
+
+/// Structure representing submission queue offsets
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct IoSqringOffsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub flags: u32,
+    pub dropped: u32,
+    pub array: u32,
+    pub resv1: u32,
+    pub resv2: u64,
+}
+
+/// Structure representing completion queue offsets
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct IoCqringOffsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub overflow: u32,
+    pub cqes: u32,
+    pub flags: u32,
+    pub resv1: u32,
+    pub resv2: u64,
+}
+
+/// Feature flags for io_uring, as defined in include/uapi/linux/io_uring.h
+const IO_URING_FEATURES: &[(u32, &str)] = &[
+    (1 << 0, "IORING_FEAT_SINGLE_MMAP"),
+    (1 << 1, "IORING_FEAT_NODROP"),
+    (1 << 2, "IORING_FEAT_SUBMIT_STABLE"),
+    (1 << 3, "IORING_FEAT_RW_CUR_POS"),
+    (1 << 4, "IORING_FEAT_CUR_PERSONALITY"),
+    (1 << 5, "IORING_FEAT_FAST_POLL"),
+    (1 << 6, "IORING_FEAT_POLL_32BITS"),
+    (1 << 7, "IORING_FEAT_SQPOLL_NONFIXED"),
+    (1 << 8, "IORING_FEAT_EXT_ARG"),
+    (1 << 9, "IORING_FEAT_NATIVE_WORKERS"),
+    (1 << 10, "IORING_FEAT_RSRC_TAGS"),
+    (1 << 11, "IORING_FEAT_CQE_SKIP"),
+    (1 << 12, "IORING_FEAT_LINKED_FILE"),
+    (1 << 13, "IORING_FEAT_REG_REG_RING"),
+    (1 << 14, "IORING_FEAT_RECVSEND_BUNDLE"),
+    (1 << 15, "IORING_FEAT_MIN_TIMEOUT"),
+    (1 << 16, "IORING_FEAT_RW_ATTR"),
+    (1 << 17, "IORING_FEAT_NO_IOWAIT"),
+];
+
+/// io_uring_register opcode that fills in a struct io_uring_probe
+const IORING_REGISTER_PROBE: u32 = 8;
+
+/// io_uring_register opcode that restricts a disabled ring to a set of
+/// register and submission opcodes
+const IORING_REGISTER_RESTRICTIONS: u32 = 11;
+
+/// io_uring_setup flag that creates a ring disabled until
+/// IORING_REGISTER_ENABLE_RINGS, the only state restrictions are accepted in
+const IORING_SETUP_R_DISABLED: u32 = 1 << 6;
+
+/// io_uring_restriction opcode allowing one submission opcode
+const IORING_RESTRICTION_SQE_OP: u16 = 1;
+
+/// Structure representing a single io_uring_restriction entry
+#[repr(C)]
+#[derive(Debug)]
+struct IoUringRestriction {
+    opcode: u16,
+    /// register_op, sqe_op or sqe_flags, depending on opcode
+    op: u8,
+    resv: u8,
+    resv2: [u32; 3],
+}
+
+/// Flag set on an io_uring_probe_op the kernel supports
+const IO_URING_OP_SUPPORTED: u16 = 1 << 0;
+
+/// Room for every opcode a u8 can name; the kernel clamps this to the
+/// number of opcodes it knows
+const IO_URING_PROBE_OPS: usize = 256;
+
+/// Structure representing a single entry of io_uring_probe
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct IoUringProbeOp {
+    op: u8,
+    resv: u8,
+    flags: u16,
+    resv2: u32,
+}
+
+/// Structure representing the IORING_REGISTER_PROBE result
+#[repr(C)]
+#[derive(Debug)]
+struct IoUringProbe {
+    last_op: u8,
+    ops_len: u8,
+    resv: u16,
+    resv2: [u32; 3],
+    ops: [IoUringProbeOp; IO_URING_PROBE_OPS],
+}
+
+/// Submission opcodes by number, as defined in include/uapi/linux/io_uring.h
+const IO_URING_OPS: &[&str] = &[
+    "IORING_OP_NOP",
+    "IORING_OP_READV",
+    "IORING_OP_WRITEV",
+    "IORING_OP_FSYNC",
+    "IORING_OP_READ_FIXED",
+    "IORING_OP_WRITE_FIXED",
+    "IORING_OP_POLL_ADD",
+    "IORING_OP_POLL_REMOVE",
+    "IORING_OP_SYNC_FILE_RANGE",
+    "IORING_OP_SENDMSG",
+    "IORING_OP_RECVMSG",
+    "IORING_OP_TIMEOUT",
+    "IORING_OP_TIMEOUT_REMOVE",
+    "IORING_OP_ACCEPT",
+    "IORING_OP_ASYNC_CANCEL",
+    "IORING_OP_LINK_TIMEOUT",
+    "IORING_OP_CONNECT",
+    "IORING_OP_FALLOCATE",
+    "IORING_OP_OPENAT",
+    "IORING_OP_CLOSE",
+    "IORING_OP_FILES_UPDATE",
+    "IORING_OP_STATX",
+    "IORING_OP_READ",
+    "IORING_OP_WRITE",
+    "IORING_OP_FADVISE",
+    "IORING_OP_MADVISE",
+    "IORING_OP_SEND",
+    "IORING_OP_RECV",
+    "IORING_OP_OPENAT2",
+    "IORING_OP_EPOLL_CTL",
+    "IORING_OP_SPLICE",
+    "IORING_OP_PROVIDE_BUFFERS",
+    "IORING_OP_REMOVE_BUFFERS",
+    "IORING_OP_TEE",
+    "IORING_OP_SHUTDOWN",
+    "IORING_OP_RENAMEAT",
+    "IORING_OP_UNLINKAT",
+    "IORING_OP_MKDIRAT",
+    "IORING_OP_SYMLINKAT",
+    "IORING_OP_LINKAT",
+    "IORING_OP_MSG_RING",
+    "IORING_OP_FSETXATTR",
+    "IORING_OP_SETXATTR",
+    "IORING_OP_FGETXATTR",
+    "IORING_OP_GETXATTR",
+    "IORING_OP_SOCKET",
+    "IORING_OP_URING_CMD",
+    "IORING_OP_SEND_ZC",
+    "IORING_OP_SENDMSG_ZC",
+    "IORING_OP_READ_MULTISHOT",
+    "IORING_OP_WAITID",
+    "IORING_OP_FUTEX_WAIT",
+    "IORING_OP_FUTEX_WAKE",
+    "IORING_OP_FUTEX_WAITV",
+    "IORING_OP_FIXED_FD_INSTALL",
+    "IORING_OP_FTRUNCATE",
+    "IORING_OP_BIND",
+    "IORING_OP_LISTEN",
+    "IORING_OP_RECV_ZC",
+    "IORING_OP_EPOLL_WAIT",
+    "IORING_OP_READV_FIXED",
+    "IORING_OP_WRITEV_FIXED",
+    "IORING_OP_PIPE",
+];
+
+/// Creates a single-entry ring with the given setup flags, returning its fd
+/// and the parameters the kernel filled in, or None if io_uring_setup is
+/// not implemented
+pub(crate) fn setup_ring(flags: u32) -> io::Result<Option<(RawFd, IoUringParams)>> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut params = IoUringParams { flags, ..Default::default() };
+        let entries: c_uint = 1;
+
+        let ret = unsafe {
+            syscall(
+                SYS_io_uring_setup,
+                entries,
+                &mut params as *mut IoUringParams,
+            )
+        };
+
+        if ret >= 0 {
+            Ok(Some((ret as RawFd, params)))
+        } else {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENOSYS) {
+                Ok(None) // System call not implemented
+            } else {
+                Err(err) // Other error occurred
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = flags;
+        Ok(None) // io_uring is not supported on non-Linux systems
+    }
+}
+
+/// Outcome of trying to create an io_uring instance
+#[derive(Debug)]
+pub enum UringStatus {
+    /// A ring was created, with the parameters the kernel filled in
+    Supported(IoUringParams),
+    /// io_uring_setup failed with ENOSYS: the kernel lacks io_uring, or a
+    /// seccomp filter rejects the call with ENOSYS
+    NotImplemented,
+    /// io_uring_setup exists but failed with EPERM, because of the
+    /// io_uring_disabled sysctl, a seccomp filter, an LSM or missing privileges
+    Blocked,
+    /// io_uring_setup exists but failed with ENOMEM, EMFILE or ENFILE: the
+    /// kernel supports io_uring but the detector hit a memlock or fd limit
+    /// allocating the probe ring
+    ResourceLimited(io::Error),
+    /// io_uring_setup failed in some other way
+    Error(io::Error),
+}
+
+impl UringStatus {
+    /// The ring parameters, if a ring could be created
+    pub fn params(&self) -> Option<&IoUringParams> {
+        match self {
+            UringStatus::Supported(params) => Some(params),
+            _ => None,
+        }
+    }
+}
+
+/// Attempts to detect if io_uring is supported on the system by creating
+/// and closing a single-entry ring
+pub fn detect_io_uring_support() -> UringStatus {
+    match setup_ring(0) {
+        Ok(Some((fd, params))) => {
+            unsafe {
+                libc::close(fd);
+            }
+            UringStatus::Supported(params)
+        }
+        Ok(None) => UringStatus::NotImplemented,
+        Err(e) if e.raw_os_error() == Some(libc::EPERM) => UringStatus::Blocked,
+        Err(e) if matches!(e.raw_os_error(), Some(libc::ENOMEM | libc::EMFILE | libc::ENFILE)) => {
+            UringStatus::ResourceLimited(e)
+        }
+        Err(e) => UringStatus::Error(e),
+    }
+}
+
+/// Asks the kernel which submission opcodes the ring behind fd supports
+///
+/// Returns (opcode, name, supported) for every opcode the kernel knows.
+/// Kernels before 5.6 lack IORING_REGISTER_PROBE and fail with EINVAL.
+pub fn probe_supported_ops(fd: RawFd) -> io::Result<Vec<(u8, String, bool)>> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut probe = IoUringProbe {
+            last_op: 0,
+            ops_len: 0,
+            resv: 0,
+            resv2: [0; 3],
+            ops: [IoUringProbeOp::default(); IO_URING_PROBE_OPS],
+        };
+
+        let ret = unsafe {
+            syscall(
+                SYS_io_uring_register,
+                fd,
+                IORING_REGISTER_PROBE,
+                &mut probe as *mut IoUringProbe,
+                IO_URING_PROBE_OPS as c_uint,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(probe.ops[..probe.ops_len as usize]
+            .iter()
+            .map(|entry| {
+                let name = IO_URING_OPS
+                    .get(entry.op as usize)
+                    .map_or_else(|| "unknown".to_string(), |name| name.to_string());
+                (entry.op, name, entry.flags & IO_URING_OP_SUPPORTED != 0)
+            })
+            .collect())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = fd;
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+/// Creates a probe ring and lists the opcodes it supports, closing the
+/// ring afterwards. Returns None if io_uring is not supported at all.
+pub fn detect_supported_ops() -> io::Result<Option<Vec<(u8, String, bool)>>> {
+    let Some((fd, _)) = setup_ring(0)? else {
+        return Ok(None);
+    };
+    let ops = probe_supported_ops(fd);
+    unsafe {
+        libc::close(fd);
+    }
+    ops.map(Some)
+}
+
+/// Checks whether the kernel supports sandboxing rings with
+/// IORING_REGISTER_RESTRICTIONS (5.10 and later)
+///
+/// Creates a ring with IORING_SETUP_R_DISABLED, registers a restriction
+/// allowing only IORING_OP_NOP on it and closes it. Kernels without
+/// restrictions reject the setup flag or the register opcode with EINVAL;
+/// None means io_uring_setup is not implemented at all.
+pub fn detect_restrictions_support() -> io::Result<Option<bool>> {
+    let fd = match setup_ring(IORING_SETUP_R_DISABLED) {
+        Ok(Some((fd, _))) => fd,
+        Ok(None) => return Ok(None),
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => return Ok(Some(false)),
+        Err(e) => return Err(e),
+    };
+
+    #[cfg(target_os = "linux")]
+    let result = {
+        let mut restriction = IoUringRestriction {
+            opcode: IORING_RESTRICTION_SQE_OP,
+            op: 0,
+            resv: 0,
+            resv2: [0; 3],
+        };
+        let ret = unsafe {
+            syscall(
+                SYS_io_uring_register,
+                fd,
+                IORING_REGISTER_RESTRICTIONS,
+                &mut restriction as *mut IoUringRestriction,
+                1 as c_uint,
+            )
+        };
+        if ret >= 0 {
+            Ok(Some(true))
+        } else {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EINVAL) {
+                Ok(Some(false))
+            } else {
+                Err(err)
+            }
+        }
+    };
+
+    #[cfg(not(target_os = "linux"))]
+    let result = Err(io::Error::from(io::ErrorKind::Unsupported));
+
+    unsafe {
+        libc::close(fd);
+    }
+    result
+}
+
+/// Expected io_uring_setup syscall number for a kernel architecture as
+/// reported by uname. Most architectures share the unified number 425;
+/// MIPS is omitted because its number depends on the userspace ABI.
+pub fn expected_io_uring_setup_nr(machine: &str) -> Option<i64> {
+    match machine {
+        "alpha" => Some(535),
+        "x86_64" | "i386" | "i486" | "i586" | "i686" | "aarch64" | "arm64"
+        | "riscv64" | "ppc64" | "ppc64le" | "ppc" | "s390x" | "loongarch64" | "arm" => Some(425),
+        m if m.starts_with("armv") => Some(425),
+        _ => None,
+    }
+}
+
+/// Searches /proc/kallsyms for the kernel's io_uring_setup entry point
+pub fn find_io_uring_setup_symbol() -> io::Result<Option<String>> {
+    let file = File::open("/proc/kallsyms")?;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if let Some(symbol) = line.split_whitespace().nth(2) {
+            if symbol.ends_with("sys_io_uring_setup") && !symbol.starts_with("__pfx_") {
+                return Ok(Some(symbol.to_string()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Splits a feature bitmask into the names of known flags and the bits
+/// that have no entry in IO_URING_FEATURES
+pub fn decode_features(features: u32) -> (Vec<&'static str>, u32) {
+    let mut names = Vec::new();
+    let mut unknown = features;
+
+    for (mask, name) in IO_URING_FEATURES {
+        if (features & mask) != 0 {
+            names.push(*name);
+            unknown &= !mask;
+        }
+    }
+
+    (names, unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feature_table_has_no_duplicates() {
+        for (i, (mask, name)) in IO_URING_FEATURES.iter().enumerate() {
+            assert_eq!(mask.count_ones(), 1, "{} is not a single bit", name);
+            for (other_mask, other_name) in &IO_URING_FEATURES[i + 1..] {
+                assert_ne!(mask, other_mask, "{} and {} share a bitmask", name, other_name);
+                assert_ne!(name, other_name, "{} is listed twice", name);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_features_empty() {
+        assert_eq!(decode_features(0), (vec![], 0));
+    }
+
+    #[test]
+    fn decode_features_single_bits() {
+        assert_eq!(decode_features(1 << 0), (vec!["IORING_FEAT_SINGLE_MMAP"], 0));
+        assert_eq!(decode_features(1 << 5), (vec!["IORING_FEAT_FAST_POLL"], 0));
+        assert_eq!(decode_features(1 << 12), (vec!["IORING_FEAT_LINKED_FILE"], 0));
+    }
+
+    #[test]
+    fn decode_features_combined_mask() {
+        let (names, unknown) = decode_features((1 << 1) | (1 << 2) | (1 << 8));
+        assert_eq!(names, vec!["IORING_FEAT_NODROP", "IORING_FEAT_SUBMIT_STABLE", "IORING_FEAT_EXT_ARG"]);
+        assert_eq!(unknown, 0);
+    }
+
+    #[test]
+    fn decode_features_all_bits() {
+        // Every known flag, through IORING_FEAT_NO_IOWAIT at bit 17, and
+        // every bit above it as unknown
+        let (names, unknown) = decode_features(u32::MAX);
+        assert_eq!(names, IO_URING_FEATURES.iter().map(|(_, name)| *name).collect::<Vec<_>>());
+        assert_eq!(names.len(), 18);
+        assert_eq!(names.last(), Some(&"IORING_FEAT_NO_IOWAIT"));
+        assert_eq!(unknown, !0x3ffff);
+    }
+
+    #[test]
+    fn decode_features_unknown_high_bits() {
+        let (names, unknown) = decode_features((1 << 0) | (1 << 30) | (1 << 31));
+        assert_eq!(names, vec!["IORING_FEAT_SINGLE_MMAP"]);
+        assert_eq!(unknown, (1 << 30) | (1 << 31));
+    }
+}
@@ -0,0 +1,778 @@
+//! Per-process details read from /proc/<pid>
+
+use std::fs::{read_dir, read_link, File};
+use std::io::{self, BufRead, BufReader};
+use std::os::fd::RawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::net::{get_connections, Connection};
+
+/// Serializes a path as a string, replacing invalid UTF-8 rather than
+/// failing, since process paths can contain arbitrary bytes
+pub(crate) fn serialize_path_lossy<S: serde::Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+/// Optional variant of serialize_path_lossy
+fn serialize_optional_path_lossy<S: serde::Serializer>(
+    path: &Option<PathBuf>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match path {
+        Some(path) => serialize_path_lossy(path, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Sequence variant of serialize_path_lossy
+pub(crate) fn serialize_paths_lossy<S: serde::Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(paths.iter().map(|path| path.to_string_lossy()))
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp with microseconds
+pub fn format_rfc3339(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let (days, day_secs) = (secs / 86400, secs % 86400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        day_secs / 3600,
+        (day_secs % 3600) / 60,
+        day_secs % 60,
+        elapsed.subsec_micros()
+    )
+}
+
+/// Serializes an optional time as an RFC 3339 string
+fn serialize_optional_time_rfc3339<S: serde::Serializer>(
+    time: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serializer.serialize_str(&format_rfc3339(*time)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Reads a /proc file, replacing any invalid UTF-8 so that a process
+/// name or argument with arbitrary bytes does not hide the whole file
+pub(crate) fn read_proc_file(path: impl AsRef<Path>) -> io::Result<String> {
+    std::fs::read(path).map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Longest name the kernel keeps in comm (TASK_COMM_LEN without the NUL)
+const COMM_MAX_LEN: usize = 15;
+
+/// Gets the raw process name from /proc/<pid>/comm, which the kernel
+/// truncates to 15 bytes
+pub fn get_process_comm(proc_root: &Path, pid: u32) -> Option<String> {
+    let path = proc_root.join(format!("{}/comm", pid));
+    read_proc_file(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Completes a truncated comm with the basename of argv[0]
+///
+/// Only a basename that extends comm is used: kernel threads have no
+/// cmdline, and processes that rewrite their argv (e.g. "nginx: worker
+/// process") or rename themselves do not match their comm, so both keep it.
+fn full_process_name(comm: &str, cmdline: Option<&[String]>) -> String {
+    if comm.len() < COMM_MAX_LEN {
+        return comm.to_string();
+    }
+    cmdline
+        .and_then(|args| args.first())
+        .and_then(|arg0| arg0.rsplit('/').next())
+        .filter(|basename| basename.len() > comm.len() && basename.starts_with(comm))
+        .unwrap_or(comm)
+        .to_string()
+}
+
+/// Gets the process name for a given PID, completed from the cmdline when
+/// comm is truncated
+pub fn get_process_name(proc_root: &Path, pid: u32) -> Option<String> {
+    let comm = get_process_comm(proc_root, pid)?;
+    if comm.len() < COMM_MAX_LEN {
+        return Some(comm);
+    }
+    let cmdline = read_proc_file(proc_root.join(format!("{}/cmdline", pid)))
+        .ok()
+        .and_then(|cmdline| parse_cmdline(&cmdline));
+    Some(full_process_name(&comm, cmdline.as_deref()))
+}
+
+/// Extracts the state character from the contents of /proc/<pid>/stat
+fn parse_stat_state(stat: &str) -> Option<char> {
+    // The comm field may itself contain spaces or parentheses, so the
+    // state is taken from after the last closing parenthesis
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    after_comm.split_whitespace().next()?.chars().next()
+}
+
+/// Extracts the start time, in clock ticks after boot, from the contents
+/// of /proc/<pid>/stat
+fn parse_stat_starttime(stat: &str) -> Option<u64> {
+    // Fields after the comm start at field 3 (state); starttime is field 22
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    after_comm.split_whitespace().nth(22 - 3)?.parse().ok()
+}
+
+/// Converts a start time in clock ticks after boot into wall-clock time,
+/// taking the boot time as now minus <proc_root>/uptime
+fn process_start_time(proc_root: &Path, start_ticks: u64) -> Option<SystemTime> {
+    let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_second <= 0 {
+        return None;
+    }
+    let uptime: f64 = read_proc_file(proc_root.join("uptime"))
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    let boot_time = SystemTime::now().checked_sub(Duration::try_from_secs_f64(uptime).ok()?)?;
+    boot_time.checked_add(Duration::from_secs_f64(start_ticks as f64 / ticks_per_second as f64))
+}
+
+/// Gets the scheduler state character for a given PID from /proc/<pid>/stat
+pub fn get_process_state(proc_root: &Path, pid: u32) -> Option<char> {
+    parse_stat_state(&read_proc_file(proc_root.join(format!("{}/stat", pid))).ok()?)
+}
+
+/// Whether an executable path read from /proc is a memfd or a file
+/// deleted from disk
+fn path_is_memory_backed(path: &str) -> bool {
+    path.ends_with(" (deleted)") || path.starts_with("/memfd:")
+}
+
+/// Gets the path of the first mapping in /proc/<pid>/maps, which is the
+/// main executable's
+///
+/// Only the first line is read, as maps of large JVM or database
+/// processes can run to many megabytes.
+fn first_mapping_path(proc_root: &Path, pid: u32) -> io::Result<Option<String>> {
+    let mut reader = BufReader::new(File::open(proc_root.join(format!("{}/maps", pid)))?);
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line)?;
+    let line = String::from_utf8_lossy(&line);
+    // None of the fields before the path contain a slash
+    Ok(line.find('/').map(|start| line[start..].trim_end().to_string()))
+}
+
+/// Picks the cgroup path from /proc/<pid>/cgroup contents, preferring the
+/// unified (v2) hierarchy unless it is only the root on a hybrid system
+fn parse_cgroup_path(cgroups: &str) -> Option<String> {
+    let unified = cgroups.lines().find_map(|line| line.strip_prefix("0::"));
+    let systemd = cgroups
+        .lines()
+        .find_map(|line| line.split_once(":name=systemd:").map(|(_, path)| path));
+    match (unified, systemd) {
+        (Some(path), _) if path != "/" => Some(path),
+        (unified, systemd) => systemd.or(unified),
+    }
+    .map(String::from)
+}
+
+/// Cgroup path fragments left by container runtimes and Kubernetes
+const CONTAINER_CGROUP_MARKERS: &[&str] = &["docker", "containerd", "kubepods", "crio", "libpod"];
+
+/// Prefixes of the per-container scope units systemd-managed runtimes create
+const CONTAINER_SCOPE_PREFIXES: &[&str] = &["docker-", "cri-containerd-", "crio-", "libpod-"];
+
+/// Extracts the container ID from a cgroup path such as /docker/<id>,
+/// /system.slice/docker-<id>.scope or /kubepods/burstable/pod<uid>/<id>
+///
+/// The ID is the innermost path component that is 64 hex digits once any
+/// runtime prefix and .scope suffix are removed.
+fn parse_container_id(cgroup: &str) -> Option<String> {
+    if !CONTAINER_CGROUP_MARKERS.iter().any(|marker| cgroup.contains(marker)) {
+        return None;
+    }
+    cgroup.rsplit('/').find_map(|component| {
+        let name = component.strip_suffix(".scope").unwrap_or(component);
+        let id = CONTAINER_SCOPE_PREFIXES
+            .iter()
+            .find_map(|prefix| name.strip_prefix(prefix))
+            .unwrap_or(name);
+        (id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())).then(|| id.to_string())
+    })
+}
+
+/// Splits the NUL separated contents of /proc/<pid>/cmdline into arguments
+fn parse_cmdline(cmdline: &str) -> Option<Vec<String>> {
+    let args: Vec<String> = cmdline
+        .split('\0')
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect();
+    (!args.is_empty()).then_some(args)
+}
+
+/// Parses the leading number of a status value such as `1996 kB`
+fn parse_numeric_prefix(value: &str) -> Option<u64> {
+    let digits = value
+        .trim_start()
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?;
+    digits.parse().ok()
+}
+
+/// Extracts the memory figures from the contents of /proc/<pid>/status
+///
+/// Fields are looked up by name rather than position, and missing fields
+/// (e.g. VmRSS for kernel threads) are left as None.
+fn parse_memory_status(status: &str) -> MemoryInfo {
+    MemoryInfo {
+        virtual_memory: proc_field(status, "VmSize").and_then(parse_numeric_prefix),
+        resident_memory: proc_field(status, "VmRSS").and_then(parse_numeric_prefix),
+    }
+}
+
+/// Extracts the real UID from the contents of /proc/<pid>/status
+fn parse_status_uid(status: &str) -> Option<u32> {
+    proc_field(status, "Uid")?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Extracts the parent PID from the contents of /proc/<pid>/status
+fn parse_status_ppid(status: &str) -> Option<u32> {
+    proc_field(status, "PPid")?.parse().ok()
+}
+
+/// Gets the parent PID of any process
+pub(crate) fn get_parent_pid(proc_root: &Path, pid: u32) -> Option<u32> {
+    parse_status_ppid(&read_proc_file(proc_root.join(format!("{}/status", pid))).ok()?)
+}
+
+/// Gets the open fds of a process with their link targets, in fd order
+fn get_open_fds(proc_root: &Path, pid: u32) -> Vec<(RawFd, String)> {
+    let Ok(fds) = read_dir(proc_root.join(format!("{}/fd", pid))) else {
+        return Vec::new();
+    };
+    let mut open_fds: Vec<(RawFd, String)> = fds
+        .flatten()
+        .filter_map(|fd_entry| {
+            let fd = fd_entry.file_name().to_string_lossy().parse().ok()?;
+            let target = read_link(fd_entry.path()).ok()?;
+            Some((fd, target.to_string_lossy().into_owned()))
+        })
+        .collect();
+    open_fds.sort_by_key(|(fd, _)| *fd);
+    open_fds
+}
+
+/// Lists the tasks (threads) of a process with their names, in TID order
+pub(crate) fn get_tasks(proc_root: &Path, pid: u32) -> Vec<(u32, String)> {
+    let Ok(tasks) = read_dir(proc_root.join(format!("{}/task", pid))) else {
+        return Vec::new();
+    };
+    let mut tasks: Vec<(u32, String)> = tasks
+        .flatten()
+        .filter_map(|task| {
+            let tid = task.file_name().to_string_lossy().parse().ok()?;
+            // Tasks may exit while being listed
+            let comm = read_proc_file(task.path().join("comm")).ok()?;
+            Some((tid, comm.trim().to_string()))
+        })
+        .collect();
+    tasks.sort_unstable();
+    tasks
+}
+
+/// Whether a task name is that of an io_uring SQPOLL (iou-sqp-*) or io-wq
+/// worker (iou-wrk-*) thread
+pub(crate) fn is_io_uring_task_name(name: &str) -> bool {
+    name.starts_with("iou-sqp-") || name.starts_with("iou-wrk-")
+}
+
+/// Classifies an fd link target as "socket", "pipe", "anon_inode" or
+/// "file", or "other" for the remaining pseudo files such as net:[...]
+pub fn fd_target_kind(target: &str) -> &'static str {
+    if target.starts_with("socket:") {
+        "socket"
+    } else if target.starts_with("pipe:") {
+        "pipe"
+    } else if target.starts_with("anon_inode:") {
+        "anon_inode"
+    } else if target.starts_with('/') {
+        "file"
+    } else {
+        "other"
+    }
+}
+
+/// Resolves a numeric UID to a user name via the system user database
+pub fn username_for_uid(uid: u32) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut passwd = unsafe { std::mem::zeroed::<libc::passwd>() };
+        let mut buf = vec![0 as libc::c_char; 4096];
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+
+        let ret = unsafe {
+            libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result)
+        };
+        if ret != 0 || result.is_null() {
+            return None;
+        }
+
+        let name = unsafe { std::ffi::CStr::from_ptr(passwd.pw_name) };
+        Some(name.to_string_lossy().into_owned())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = uid;
+        None
+    }
+}
+
+/// Gets detailed process information including command line arguments and memory status
+///
+/// The namespace and root comparisons are against the detector itself, so
+/// they always use the host's /proc/self whatever `proc_root` is.
+pub(crate) fn get_process_info(proc_root: &Path, pid: u32) -> ProcessInfo {
+    let pid_dir = proc_root.join(pid.to_string());
+    let comm = get_process_comm(proc_root, pid).unwrap_or_else(|| "<unknown>".to_string());
+    let mut info = ProcessInfo {
+        name: comm.clone(),
+        comm,
+        exe_path: None,
+        cmdline: None,
+        memory_status: None,
+        exe_is_memory_backed: false,
+        state: get_process_state(proc_root, pid).unwrap_or('?'),
+        mount_ns_differs: false,
+        root_differs: false,
+        uid: None,
+        username: None,
+        cgroup: None,
+        container_id: None,
+        start_time: None,
+        ppid: None,
+        open_fds: get_open_fds(proc_root, pid),
+        threads: Vec::new(),
+        io_uring_threads: Vec::new(),
+        connections: Vec::new(),
+    };
+    info.connections = get_connections(proc_root, pid, &info.open_fds);
+
+    for (tid, name) in get_tasks(proc_root, pid) {
+        if is_io_uring_task_name(&name) {
+            info.io_uring_threads.push((tid, name));
+        }
+        info.threads.push(tid);
+    }
+
+    // Paths read from the process's links are only meaningful inside its
+    // own mount namespace
+    if let (Ok(own_ns), Ok(target_ns)) = (
+        read_link("/proc/self/ns/mnt"),
+        read_link(pid_dir.join("ns/mnt")),
+    ) {
+        info.mount_ns_differs = own_ns != target_ns;
+    }
+
+    // Compare the root directories themselves, since the link text of a
+    // chroot can read as "/" from inside a container
+    if let (Ok(own_root), Ok(target_root)) = (
+        std::fs::metadata("/proc/self/root"),
+        std::fs::metadata(pid_dir.join("root")),
+    ) {
+        use std::os::unix::fs::MetadataExt;
+        info.root_differs = (own_root.dev(), own_root.ino()) != (target_root.dev(), target_root.ino());
+    }
+
+    // Get executable path
+    if let Ok(path) = read_link(pid_dir.join("exe")) {
+        info.exe_path = Some(path);
+    }
+
+    // Get command line arguments
+    if let Ok(cmdline) = read_proc_file(pid_dir.join("cmdline")) {
+        info.cmdline = parse_cmdline(&cmdline);
+    }
+    info.name = full_process_name(&info.comm, info.cmdline.as_deref());
+
+    // The exe link is authoritative; the executable's own mapping is the
+    // fallback when the link cannot be read
+    info.exe_is_memory_backed = match &info.exe_path {
+        Some(path) => path_is_memory_backed(&path.to_string_lossy()),
+        None => first_mapping_path(proc_root, pid)
+            .ok()
+            .flatten()
+            .is_some_and(|path| path_is_memory_backed(&path)),
+    };
+
+    if let Ok(stat) = read_proc_file(pid_dir.join("stat")) {
+        info.start_time = parse_stat_starttime(&stat).and_then(|ticks| process_start_time(proc_root, ticks));
+    }
+
+    if let Ok(cgroups) = read_proc_file(pid_dir.join("cgroup")) {
+        info.cgroup = parse_cgroup_path(&cgroups);
+        info.container_id = info.cgroup.as_deref().and_then(parse_container_id);
+    }
+
+    // Get memory status and owner
+    if let Ok(status) = read_proc_file(pid_dir.join("status")) {
+        info.memory_status = Some(parse_memory_status(&status));
+        info.uid = parse_status_uid(&status);
+        info.username = info.uid.and_then(username_for_uid);
+        info.ppid = parse_status_ppid(&status);
+    }
+
+    info
+}
+
+/// Structure to hold process information
+#[derive(Debug, Default, Serialize)]
+pub struct ProcessInfo {
+    /// comm, completed from argv[0] when the kernel truncated it
+    pub name: String,
+    /// Raw name from /proc/<pid>/comm, at most 15 bytes
+    pub comm: String,
+    #[serde(serialize_with = "serialize_optional_path_lossy")]
+    pub exe_path: Option<PathBuf>,
+    pub cmdline: Option<Vec<String>>,
+    pub memory_status: Option<MemoryInfo>,
+    /// Whether the executable is a memfd or deleted from disk, as with
+    /// fileless execution
+    pub exe_is_memory_backed: bool,
+    pub state: char,
+    /// Whether the process lives in a different mount namespace than the detector
+    pub mount_ns_differs: bool,
+    /// Whether the process's root directory differs from the detector's,
+    /// e.g. because it runs in a chroot
+    pub root_differs: bool,
+    /// Real UID of the process owner
+    pub uid: Option<u32>,
+    /// User name the owner UID resolves to
+    pub username: Option<String>,
+    /// Cgroup path, from the cgroup v2 entry or the v1 name=systemd one
+    pub cgroup: Option<String>,
+    /// Docker, containerd, CRI-O or Podman container ID, from the cgroup path
+    pub container_id: Option<String>,
+    /// When the process started, derived from its stat starttime
+    #[serde(serialize_with = "serialize_optional_time_rfc3339")]
+    pub start_time: Option<SystemTime>,
+    pub ppid: Option<u32>,
+    /// Every open fd with its link target, io_uring rings included
+    pub open_fds: Vec<(RawFd, String)>,
+    /// TIDs of all the process's tasks
+    pub threads: Vec<u32>,
+    /// TIDs and names of the tasks that are io_uring SQPOLL or io-wq threads
+    pub io_uring_threads: Vec<(u32, String)>,
+    /// TCP and UDP sockets among the open fds, with their addresses
+    pub connections: Vec<Connection>,
+}
+
+/// Scheduling parameters of a task, from /proc/<tid>/stat
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SchedInfo {
+    /// SCHED_* policy number
+    pub policy: u32,
+    /// Real-time priority, 0 for non-real-time policies
+    pub rt_priority: u32,
+    pub nice: i32,
+}
+
+impl SchedInfo {
+    /// Name of the scheduling policy
+    pub fn policy_name(&self) -> &'static str {
+        match self.policy {
+            0 => "SCHED_OTHER",
+            1 => "SCHED_FIFO",
+            2 => "SCHED_RR",
+            3 => "SCHED_BATCH",
+            5 => "SCHED_IDLE",
+            6 => "SCHED_DEADLINE",
+            7 => "SCHED_EXT",
+            _ => "unknown",
+        }
+    }
+
+    /// Whether the policy is one of the real-time ones
+    pub fn is_realtime(&self) -> bool {
+        matches!(self.policy, 1 | 2)
+    }
+}
+
+/// Parses nice (field 19), rt_priority (40) and policy (41) from a
+/// /proc/<tid>/stat line
+pub(crate) fn parse_stat_sched(stat: &str) -> Option<SchedInfo> {
+    // Fields after the comm start at field 3 (state)
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    Some(SchedInfo {
+        nice: fields.get(19 - 3)?.parse().ok()?,
+        rt_priority: fields.get(40 - 3)?.parse().ok()?,
+        policy: fields.get(41 - 3)?.parse().ok()?,
+    })
+}
+
+/// Structure to hold memory information
+#[derive(Debug, Default, Serialize)]
+pub struct MemoryInfo {
+    pub virtual_memory: Option<u64>,
+    pub resident_memory: Option<u64>,
+}
+
+/// Looks up a `Key: value` field in status-style /proc contents such as
+/// /proc/<pid>/status or fdinfo, tolerating any whitespace around both
+pub(crate) fn proc_field<'a>(contents: &'a str, key: &str) -> Option<&'a str> {
+    contents.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        (name.trim() == key).then(|| value.trim())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT: &str = "1234 (my (weird) proc) S 1 1234 1234 0 -1 4194560 1001 0 0 0 12 3 0 0 20 0 4 0 \
+                        5678 123456789 2048 18446744073709551615 1 1 0 0 0 0 0 4096 16384 0 0 0 17 2 50 1 0 0 0";
+
+    const STATUS: &str = "Name:\tnginx\nState:\tS (sleeping)\nPPid:\t1\nUid:\t33\t33\t33\t33\n\
+                          VmSize:\t  123456 kB\nVmRSS:\t   78901 kB\n";
+
+    const CGROUP: &str = "12:name=systemd:/system.slice/docker-0123.scope\n0::/system.slice/nginx.service\n";
+
+    const CMDLINE: &str = "/usr/sbin/nginx\0-g\0daemon off;\0";
+
+    /// Runs every /proc content parser over the input; they may return
+    /// anything but must not panic
+    fn parse_everything(input: &str) {
+        let _ = parse_stat_state(input);
+        let _ = parse_stat_starttime(input);
+        let _ = parse_stat_sched(input);
+        let _ = parse_cmdline(input);
+        let _ = parse_memory_status(input);
+        let _ = parse_status_uid(input);
+        let _ = parse_status_ppid(input);
+        if let Some(cgroup) = parse_cgroup_path(input) {
+            let _ = parse_container_id(&cgroup);
+        }
+        let _ = parse_container_id(input);
+    }
+
+    /// Excerpt of /proc/<pid>/status from a 3.10 (RHEL 7) kernel
+    const STATUS_3_10: &str = "Name:\tmysqld\nState:\tS (sleeping)\nTgid:\t2201\nPid:\t2201\nPPid:\t1\n\
+                               Uid:\t27\t27\t27\t27\nVmPeak:\t 1153812 kB\nVmSize:\t 1088276 kB\n\
+                               VmLck:\t       0 kB\nVmHWM:\t  122140 kB\nVmRSS:\t  122140 kB\nVmData:\t  981212 kB\n";
+
+    /// Excerpt from a 5.15 kernel, which splits RSS into RssAnon, RssFile
+    /// and RssShmem after VmRSS
+    const STATUS_5_15: &str = "Name:\tnginx\nUmask:\t0022\nState:\tS (sleeping)\nPPid:\t1\n\
+                               Uid:\t33\t33\t33\t33\nVmPeak:\t  123900 kB\nVmSize:\t  123456 kB\n\
+                               VmRSS:\t   78901 kB\nRssAnon:\t   60000 kB\nRssFile:\t   18901 kB\n\
+                               RssShmem:\t       0 kB\n";
+
+    /// Excerpt from a 6.8 kernel thread: no Vm* fields at all
+    const STATUS_6_8_KTHREAD: &str = "Name:\tkworker/0:1-events\nUmask:\t0000\nState:\tI (idle)\n\
+                                      PPid:\t2\nUid:\t0\t0\t0\t0\nKthread:\t1\nThreads:\t1\n";
+
+    /// Hand-edited status with spaces instead of tabs and no unit suffix,
+    /// as seen from some emulated /proc implementations
+    const STATUS_SPACES: &str = "Name: app\nPPid:   7\nUid:   1000   1000   1000   1000\n\
+                                 VmSize:   4096\nVmRSS:   2048\n";
+
+    #[test]
+    fn memory_status_across_kernels() {
+        let cases = [
+            (STATUS_3_10, Some(1088276), Some(122140)),
+            (STATUS_5_15, Some(123456), Some(78901)),
+            (STATUS_6_8_KTHREAD, None, None),
+            (STATUS_SPACES, Some(4096), Some(2048)),
+        ];
+        for (status, virtual_memory, resident_memory) in cases {
+            let memory = parse_memory_status(status);
+            assert_eq!(memory.virtual_memory, virtual_memory, "{}", status);
+            assert_eq!(memory.resident_memory, resident_memory, "{}", status);
+        }
+    }
+
+    #[test]
+    fn status_uid_and_ppid_across_kernels() {
+        assert_eq!(parse_status_uid(STATUS_3_10), Some(27));
+        assert_eq!(parse_status_ppid(STATUS_3_10), Some(1));
+        assert_eq!(parse_status_uid(STATUS_5_15), Some(33));
+        assert_eq!(parse_status_uid(STATUS_6_8_KTHREAD), Some(0));
+        assert_eq!(parse_status_ppid(STATUS_6_8_KTHREAD), Some(2));
+        assert_eq!(parse_status_uid(STATUS_SPACES), Some(1000));
+        assert_eq!(parse_status_ppid(STATUS_SPACES), Some(7));
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn full_name_completes_truncated_comm() {
+        let cmdline = args(&["/usr/lib/systemd/systemd-resolved", "--debug"]);
+        assert_eq!(full_process_name("systemd-resolve", Some(&cmdline)), "systemd-resolved");
+        let cmdline = args(&["io_uring_long_named_server"]);
+        assert_eq!(full_process_name("io_uring_long_n", Some(&cmdline)), "io_uring_long_named_server");
+    }
+
+    #[test]
+    fn full_name_keeps_short_comm() {
+        let cmdline = args(&["/usr/sbin/nginx-debug-build"]);
+        assert_eq!(full_process_name("nginx", Some(&cmdline)), "nginx");
+    }
+
+    #[test]
+    fn full_name_keeps_comm_when_argv0_differs() {
+        let cmdline = args(&["nginx: worker process"]);
+        assert_eq!(full_process_name("a_long_threadnm", Some(&cmdline)), "a_long_threadnm");
+        let cmdline = args(&["/usr/bin/systemd-resolve"]);
+        assert_eq!(full_process_name("systemd-resolve", Some(&cmdline)), "systemd-resolve");
+    }
+
+    #[test]
+    fn full_name_keeps_comm_without_cmdline() {
+        assert_eq!(full_process_name("kworker/u16:2-e", None), "kworker/u16:2-e");
+        assert_eq!(full_process_name("systemd-resolve", Some(&[])), "systemd-resolve");
+        assert_eq!(full_process_name("systemd-resolve", parse_cmdline("").as_deref()), "systemd-resolve");
+    }
+
+    #[test]
+    fn process_info_from_fake_proc() {
+        // The comm holds ") " so that only parsing from the last ')' of
+        // stat finds the state
+        let root = std::env::temp_dir().join(format!("io_uring_detector-info-{}", std::process::id()));
+        let pid_dir = root.join("1234");
+        let container = "0123456789abcdef".repeat(4);
+        std::fs::create_dir_all(pid_dir.join("fd")).unwrap();
+        std::fs::create_dir_all(pid_dir.join("task/1234")).unwrap();
+        std::fs::create_dir_all(pid_dir.join("task/1240")).unwrap();
+        std::fs::write(pid_dir.join("comm"), "ring) srv (x\n").unwrap();
+        std::fs::write(
+            pid_dir.join("stat"),
+            "1234 (ring) srv (x) D 1 1234 1234 0 -1 4194560 1001 0 0 0 12 3 0 0 20 0 2 0 \
+             5678 123456789 2048 18446744073709551615 1 1 0 0 0 0 0 4096 16384 0 0 0 17 2 0 0 0 0 0",
+        )
+        .unwrap();
+        std::fs::write(pid_dir.join("status"), STATUS).unwrap();
+        std::fs::write(pid_dir.join("cmdline"), "/opt/ring srv\0--port\08080\0").unwrap();
+        std::fs::write(pid_dir.join("cgroup"), format!("0::/system.slice/docker-{}.scope\n", container)).unwrap();
+        std::fs::write(pid_dir.join("task/1234/comm"), "ring) srv (x\n").unwrap();
+        std::fs::write(pid_dir.join("task/1240/comm"), "iou-wrk-1234\n").unwrap();
+        std::fs::write(root.join("uptime"), "1000.00 500.00\n").unwrap();
+        std::os::unix::fs::symlink("anon_inode:[io_uring]", pid_dir.join("fd/4")).unwrap();
+        std::os::unix::fs::symlink("/var/log/ring.log", pid_dir.join("fd/1")).unwrap();
+
+        let info = get_process_info(&root, 1234);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(info.comm, "ring) srv (x");
+        assert_eq!(info.name, "ring) srv (x");
+        assert_eq!(info.state, 'D');
+        assert_eq!(info.cmdline, Some(args(&["/opt/ring srv", "--port", "8080"])));
+        assert_eq!(info.uid, Some(33));
+        assert_eq!(info.ppid, Some(1));
+        let memory = info.memory_status.expect("status has memory fields");
+        assert_eq!(memory.virtual_memory, Some(123456));
+        assert_eq!(memory.resident_memory, Some(78901));
+        assert_eq!(info.cgroup, Some(format!("/system.slice/docker-{}.scope", container)));
+        assert_eq!(info.container_id, Some(container));
+        assert!(info.start_time.is_some());
+        assert_eq!(
+            info.open_fds,
+            vec![(1, String::from("/var/log/ring.log")), (4, String::from("anon_inode:[io_uring]"))]
+        );
+        assert_eq!(info.threads, vec![1234, 1240]);
+        assert_eq!(info.io_uring_threads, vec![(1240, String::from("iou-wrk-1234"))]);
+    }
+
+    #[test]
+    fn parsers_survive_malformed_input() {
+        let cases = [
+            "",
+            " ",
+            ")",
+            "(",
+            "()",
+            "1 (comm) ",
+            "1 (comm)",
+            "1 (a) b) c) R",
+            "1 ()) R 0",
+            "1 (comm) R x x x",
+            ":",
+            "::",
+            "0::",
+            "Uid:",
+            "Uid:\t",
+            "VmRSS:",
+            "VmRSS: kB",
+            "VmSize: 99999999999999999999999 kB",
+            "PPid: -1",
+            "\0\0\0",
+            "name=systemd:",
+            "/docker/",
+            "/system.slice/docker-.scope",
+            "\u{fffd}\u{fffd})\u{fffd}",
+        ];
+        for case in cases {
+            parse_everything(case);
+        }
+    }
+
+    #[test]
+    fn parsers_survive_truncated_input() {
+        for sample in [STAT, STATUS, CGROUP, CMDLINE] {
+            for (end, _) in sample.char_indices() {
+                parse_everything(&sample[..end]);
+                parse_everything(&sample[end..]);
+            }
+        }
+    }
+
+    #[test]
+    fn parsers_survive_arbitrary_bytes() {
+        // xorshift64, so every run feeds the same inputs
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        // Bias towards the bytes the parsers split on
+        const INTERESTING: &[u8] = b"() :\0\n\t-/0123456789kBRSZ";
+
+        for _ in 0..5000 {
+            let len = (next() % 256) as usize;
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| {
+                    let value = next();
+                    if value % 2 == 0 {
+                        INTERESTING[(value >> 8) as usize % INTERESTING.len()]
+                    } else {
+                        (value >> 8) as u8
+                    }
+                })
+                .collect();
+            parse_everything(&String::from_utf8_lossy(&bytes));
+        }
+    }
+}
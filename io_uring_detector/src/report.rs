@@ -0,0 +1,147 @@
+//! The whole detector run as a single value, for library users
+
+use std::io;
+
+use serde::Serialize;
+
+use crate::probe::{decode_features, detect_io_uring_support, detect_restrictions_support, UringStatus};
+use crate::process::get_process_name;
+use crate::scan::{proc_root, scan_processes, Detection, ScanOptions, ScanStatus};
+use crate::system::{support_reason, system_info, SystemInfo};
+
+/// Version of the serialized Report layout, see [`Report::schema_version`]
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Everything the detector finds in one run: the host, io_uring support
+/// and the processes using it
+///
+/// The serialized form carries `schema_version` so consumers can tell
+/// which layout they are reading. It is bumped whenever a field is
+/// removed, renamed or changes meaning. Adding a field does not bump it,
+/// so parsers should ignore fields they do not know.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// Always [`SCHEMA_VERSION`]
+    pub schema_version: u32,
+    pub system: SystemInfo,
+    /// Whether the detector could create an io_uring instance
+    pub supported: bool,
+    /// Names of the feature flags reported by io_uring_setup
+    pub features: Vec<String>,
+    /// Feature bits missing from the detector's feature table
+    pub unknown_feature_bits: u32,
+    /// Whether rings can be sandboxed with IORING_REGISTER_RESTRICTIONS
+    pub restrictions_supported: bool,
+    /// Processes using io_uring, empty when io_uring is unsupported or no
+    /// scan was made
+    ///
+    /// These are Detections rather than bare ProcessInfo because the PID,
+    /// the rings and how they were found are not part of ProcessInfo; the
+    /// process details are each Detection's `info`.
+    pub processes: Vec<Detection>,
+    /// Processes whose fds could not be read for lack of permission
+    pub permission_denied_count: usize,
+    /// Processes that exited during the scan, before they could be read
+    pub vanished_count: usize,
+    /// Processes left out of `processes` because an earlier one ran the
+    /// same executable, present only when once_per_exe was asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppressed_duplicates: Option<usize>,
+    /// Every scanned process, present only when include_all_processes was
+    /// asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub all_processes: Option<Vec<ScannedProcess>>,
+    /// Outcome of the io_uring_setup probe, with the ring parameters when
+    /// it succeeded or the reason it did not
+    #[serde(skip)]
+    pub probe: UringStatus,
+    /// Every scanned PID with whether it uses io_uring
+    #[serde(skip)]
+    pub inventory: Vec<(u32, ScanStatus)>,
+}
+
+/// A scanned process and whether it uses io_uring
+#[derive(Debug, Serialize)]
+pub struct ScannedProcess {
+    pub pid: u32,
+    pub name: Option<String>,
+    /// None when it could not be told, for the reason given by `status`
+    pub uses_io_uring: Option<bool>,
+    pub status: ScanStatus,
+}
+
+/// Runs the support probe and a process scan with the default options
+pub fn run() -> io::Result<Report> {
+    run_with(&ScanOptions::default())
+}
+
+/// Runs the support probe and a process scan, skipping the scan when
+/// io_uring is unsupported
+///
+/// io_uring being unavailable is part of the report rather than an error,
+/// so only failures to gather the data make the run fail.
+pub fn run_with(options: &ScanOptions) -> io::Result<Report> {
+    let mut report = run_without_scan()?;
+    if report.supported {
+        let result = scan_processes(options).map_err(|e| {
+            let root = proc_root(options.root.as_deref());
+            io::Error::new(e.kind(), format!("could not read {}: {}", root.display(), e))
+        })?;
+        report.permission_denied_count = result.permission_denied_count();
+        report.vanished_count = result.vanished_count();
+        report.processes = result.detections;
+        report.inventory = result.inventory;
+        report.suppressed_duplicates = options.once_per_exe.then_some(result.suppressed_duplicates);
+    } else if options.once_per_exe {
+        report.suppressed_duplicates = Some(0);
+    }
+    if options.include_all_processes {
+        let root = proc_root(options.root.as_deref());
+        report.all_processes = Some(
+            report
+                .inventory
+                .iter()
+                .map(|&(pid, status)| ScannedProcess {
+                    pid,
+                    name: get_process_name(&root, pid),
+                    uses_io_uring: status.uses_io_uring(),
+                    status,
+                })
+                .collect(),
+        );
+    }
+    Ok(report)
+}
+
+/// Runs the support probe alone, leaving the process list empty
+pub fn run_without_scan() -> io::Result<Report> {
+    let mut system = system_info()
+        .map_err(|e| io::Error::new(e.kind(), format!("could not get system information: {}", e)))?;
+    let probe = detect_io_uring_support();
+    system.reason_code = support_reason(&probe, &system);
+    if let UringStatus::Error(e) = &probe {
+        return Err(io::Error::new(e.kind(), format!("io_uring_setup failed: {}", e)));
+    }
+    system.io_uring_support = probe.params().is_some();
+
+    let (features, unknown_feature_bits) = probe
+        .params()
+        .map(|params| decode_features(params.features))
+        .unwrap_or_default();
+
+    Ok(Report {
+        schema_version: SCHEMA_VERSION,
+        supported: system.io_uring_support,
+        system,
+        features: features.into_iter().map(String::from).collect(),
+        unknown_feature_bits,
+        restrictions_supported: probe.params().is_some() && matches!(detect_restrictions_support(), Ok(Some(true))),
+        processes: Vec::new(),
+        permission_denied_count: 0,
+        vanished_count: 0,
+        suppressed_duplicates: None,
+        all_processes: None,
+        probe,
+        inventory: Vec::new(),
+    })
+}
@@ -0,0 +1,475 @@
+//! Finding io_uring fds and parsing their fdinfo
+
+use std::fs::{read_dir, read_link, File};
+use std::io::{self, BufRead, BufReader};
+use std::os::fd::RawFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::process::{
+    parse_stat_sched, proc_field, read_proc_file, serialize_paths_lossy, SchedInfo,
+};
+
+/// Result of checking a single process for io_uring file descriptors
+#[derive(Debug, Default)]
+pub struct ProcessUsage {
+    pub uses_io_uring: bool,
+    /// The io_uring fds found, each with the TID of the task whose fd table
+    /// holds it (the PID itself for the process's own table), ordered by
+    /// TID and then fd
+    pub ring_fds: Vec<(u32, RawFd)>,
+}
+
+/// NAPI busy-poll settings of a ring, as exposed in fdinfo by newer kernels
+#[derive(Debug, Default, Serialize)]
+pub struct NapiInfo {
+    pub enabled: bool,
+    pub busy_poll_timeout_us: Option<u64>,
+    pub prefer_busy_poll: Option<bool>,
+}
+
+/// Details of a single io_uring ring parsed from /proc/<pid>/fdinfo/<fd>
+#[derive(Debug, Default, Serialize)]
+pub struct RingInfo {
+    pub fd: RawFd,
+    /// Inode of the ring file, unique per ring on kernels 5.12 and later
+    pub inode: Option<u64>,
+    /// PID of the submission queue polling thread, if the ring has one
+    pub sq_thread: Option<i32>,
+    pub sq_thread_cpu: Option<i32>,
+    /// Idle timeout in milliseconds, only exposed by some kernels
+    pub sq_thread_idle: Option<u32>,
+    /// Scheduling policy and priority of the polling thread
+    pub sq_thread_sched: Option<SchedInfo>,
+    /// Number of submission queue entries, derived from SqMask
+    pub sq_entries: Option<u32>,
+    /// Number of completion queue entries, derived from CqMask
+    pub cq_entries: Option<u32>,
+    /// None when the kernel does not report NAPI state for rings
+    pub napi: Option<NapiInfo>,
+    /// Files registered with IORING_REGISTER_FILES, as paths in the
+    /// process's namespace (sockets appear as `socket:[inode]`)
+    pub registered_files: Vec<String>,
+    /// Number of buffers registered with IORING_REGISTER_BUFFERS
+    pub registered_buffers: Option<u32>,
+    /// Total size of the registered buffers, which stay pinned in memory
+    pub registered_buffer_bytes: Option<u64>,
+    /// Registered files that are block devices or raw/passthrough devices
+    #[serde(serialize_with = "serialize_paths_lossy")]
+    pub backing_devices: Vec<PathBuf>,
+    /// Coarse guess at what the ring is used for: "network", "disk" or "mixed"
+    pub inferred_workload: Option<String>,
+    /// Completions waiting on the overflow list because the CQ ring was full,
+    /// None on kernels whose fdinfo does not list them
+    pub cq_overflow: Option<u64>,
+}
+
+impl RingInfo {
+    /// Whether the ring was set up with a submission queue polling thread
+    pub fn uses_sqpoll(&self) -> bool {
+        matches!(self.sq_thread, Some(tid) if tid >= 0)
+    }
+
+    /// Whether the ring is a tiny one typically created only to probe for
+    /// io_uring support, rather than to carry a real workload
+    pub fn is_probe_ring(&self) -> bool {
+        matches!(self.sq_entries, Some(entries) if entries <= 2)
+    }
+}
+
+/// Whether an fdinfo line starts a new `Key:` section, as opposed to an
+/// entry of the current one
+fn is_section_header(line: &str) -> bool {
+    line.split_once(':').is_some_and(|(key, _)| {
+        key.starts_with(|c: char| c.is_ascii_alphabetic())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Returns the `<index>: <value>` entries listed below a `Key: count` line
+/// in fdinfo, as used for UserFiles and UserBufs
+fn proc_field_entries<'a>(contents: &'a str, key: &str) -> Vec<&'a str> {
+    let mut lines = contents.lines();
+    let header = format!("{}:", key);
+    if !lines.any(|line| line.starts_with(&header)) {
+        return Vec::new();
+    }
+
+    // Indices are printed as %5u, so from 10000 on the entries are no
+    // longer indented; only the next section header ends the list
+    lines
+        .take_while(|line| !is_section_header(line))
+        .filter_map(|line| {
+            let (index, value) = line.trim_start().split_once(": ")?;
+            index.parse::<u32>().ok().map(|_| value)
+        })
+        .collect()
+}
+
+/// Sums the lengths of the `<addr>/<len>` entries listed below UserBufs,
+/// None if any entry cannot be parsed
+pub fn registered_buffer_bytes(contents: &str) -> Option<u64> {
+    proc_field(contents, "UserBufs")?;
+    proc_field_entries(contents, "UserBufs")
+        .into_iter()
+        .map(|entry| entry.rsplit_once('/')?.1.parse::<u64>().ok())
+        .sum()
+}
+
+/// Counts the `user_data=..., res=..., flags=...` entries listed below
+/// CqOverflowList in fdinfo
+fn count_cq_overflow(contents: &str) -> Option<u64> {
+    let mut lines = contents.lines();
+    if !lines.any(|line| line.starts_with("CqOverflowList:")) {
+        return None;
+    }
+
+    let entries = lines
+        .take_while(|line| line.starts_with(' ') || line.starts_with('\t'))
+        .filter(|line| line.trim_start().starts_with("user_data="))
+        .count();
+    Some(entries as u64)
+}
+
+/// Resolves an absolute path as seen by a process with the given root
+pub(crate) fn resolve_in_root(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// Converts a path as rendered in fdinfo and fd links, which is relative to
+/// the detector's root, into the path the process itself uses; the two
+/// differ when the process is chrooted below the detector's root
+fn path_in_process_root(root: &Path, path: &Path) -> PathBuf {
+    match read_link(root) {
+        Ok(process_root) if process_root != Path::new("/") => match path.strip_prefix(&process_root) {
+            Ok(relative) => Path::new("/").join(relative),
+            Err(_) => path.to_path_buf(),
+        },
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Whether a registered file gives the ring direct access to storage:
+/// a block device, an NVMe generic char device, or a raw/SCSI generic node
+///
+/// `root` is the process's `/proc/<pid>/root`. Paths that cannot be reached
+/// from the detector's root are looked up through it instead.
+fn is_backing_device(root: &Path, path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    let process_path = path_in_process_root(root, path);
+    let name = process_path.to_string_lossy();
+    if !name.starts_with("/dev/") {
+        return false;
+    }
+    let metadata = std::fs::metadata(path)
+        .or_else(|_| std::fs::metadata(resolve_in_root(root, &process_path)));
+    if let Ok(metadata) = metadata {
+        if metadata.file_type().is_block_device() {
+            return true;
+        }
+    }
+    name.starts_with("/dev/ng") || name.starts_with("/dev/sg") || name.starts_with("/dev/raw/")
+}
+
+/// Classifies an fd or registered file target as network or disk I/O,
+/// returning None for anything else (pipes, anon inodes, ttys, /dev/null)
+fn classify_io_target(root: &Path, target: &str) -> Option<&'static str> {
+    if target.starts_with("socket:") {
+        Some("network")
+    } else if !target.starts_with('/') {
+        None
+    } else if path_in_process_root(root, Path::new(target)).starts_with("/dev") {
+        is_backing_device(root, Path::new(target)).then_some("disk")
+    } else {
+        Some("disk")
+    }
+}
+
+/// Infers a ring's workload from the kinds of files it can reach
+pub(crate) fn infer_workload<'a>(root: &Path, targets: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let mut network = false;
+    let mut disk = false;
+    for target in targets {
+        match classify_io_target(root, target) {
+            Some("network") => network = true,
+            Some(_) => disk = true,
+            None => {}
+        }
+    }
+
+    match (network, disk) {
+        (true, true) => Some(String::from("mixed")),
+        (true, false) => Some(String::from("network")),
+        (false, true) => Some(String::from("disk")),
+        (false, false) => None,
+    }
+}
+
+/// Parses the NAPI busy-poll section of an io_uring fdinfo file
+fn parse_napi_fdinfo(contents: &str) -> Option<NapiInfo> {
+    let enabled = proc_field(contents, "NAPI")? == "enabled";
+
+    // Older kernels report the timeout in microseconds, newer ones as a
+    // nanosecond delta
+    let busy_poll_timeout_us = proc_field(contents, "napi_busy_poll_to")
+        .and_then(|v| v.parse().ok())
+        .or_else(|| {
+            proc_field(contents, "napi_busy_poll_dt")
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(|ns| ns / 1000)
+        });
+
+    Some(NapiInfo {
+        enabled,
+        busy_poll_timeout_us,
+        prefer_busy_poll: proc_field(contents, "napi_prefer_busy_poll").map(|v| v == "true"),
+    })
+}
+
+/// Converts a hex SqMask or CqMask value into the number of queue entries
+fn parse_ring_mask(value: &str) -> Option<u32> {
+    u32::from_str_radix(value.trim_start_matches("0x"), 16)
+        .ok()
+        .map(|mask| mask + 1)
+}
+
+/// Parses the ring details from the contents of an io_uring fdinfo file
+fn parse_ring_fdinfo(fd: RawFd, contents: &str) -> RingInfo {
+    RingInfo {
+        fd,
+        inode: proc_field(contents, "ino").and_then(|v| v.parse().ok()),
+        sq_thread: proc_field(contents, "SqThread").and_then(|v| v.parse().ok()),
+        sq_thread_cpu: proc_field(contents, "SqThreadCpu").and_then(|v| v.parse().ok()),
+        sq_thread_idle: proc_field(contents, "SqThreadIdle").and_then(|v| v.parse().ok()),
+        sq_thread_sched: None,
+        sq_entries: proc_field(contents, "SqMask").and_then(parse_ring_mask),
+        cq_entries: proc_field(contents, "CqMask").and_then(parse_ring_mask),
+        napi: parse_napi_fdinfo(contents),
+        registered_files: proc_field_entries(contents, "UserFiles")
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        registered_buffers: proc_field(contents, "UserBufs").and_then(|v| v.parse().ok()),
+        registered_buffer_bytes: registered_buffer_bytes(contents),
+        backing_devices: Vec::new(),
+        inferred_workload: None,
+        cq_overflow: count_cq_overflow(contents),
+    }
+}
+
+/// Gets the ring details for an io_uring fd in the fd table of task `tid`
+/// of a process, `pid` itself for the process's own table
+pub(crate) fn get_ring_info(proc_root: &Path, pid: u32, tid: u32, fd: RawFd) -> RingInfo {
+    let path = if tid == pid {
+        proc_root.join(format!("{}/fdinfo/{}", pid, fd))
+    } else {
+        proc_root.join(format!("{}/task/{}/fdinfo/{}", pid, tid, fd))
+    };
+    match read_proc_file(path) {
+        Ok(contents) => {
+            let mut ring = parse_ring_fdinfo(fd, &contents);
+            if let Some(tid) = ring.sq_thread.filter(|&tid| tid > 0) {
+                ring.sq_thread_sched = read_proc_file(proc_root.join(format!("{}/stat", tid)))
+                    .ok()
+                    .and_then(|stat| parse_stat_sched(&stat));
+            }
+            let root = proc_root.join(format!("{}/root", pid));
+            ring.backing_devices = ring
+                .registered_files
+                .iter()
+                .map(PathBuf::from)
+                .filter(|path| is_backing_device(&root, path))
+                .collect();
+            ring
+        }
+        Err(_) => RingInfo {
+            fd,
+            ..Default::default()
+        },
+    }
+}
+
+/// Lists the io_uring fds in a single fd directory, in ascending order,
+/// with the inode of each ring (None when the link cannot be followed)
+fn collect_ring_fds(fd_dir: &Path) -> io::Result<Vec<(RawFd, Option<u64>)>> {
+    let mut ring_fds = Vec::new();
+    for fd_entry in read_dir(fd_dir)?.flatten() {
+        if let Ok(link_target) = read_link(fd_entry.path()) {
+            if link_target.to_string_lossy().contains("anon_inode:[io_uring]") {
+                if let Ok(fd) = fd_entry.file_name().to_string_lossy().parse::<RawFd>() {
+                    let inode = std::fs::metadata(fd_entry.path()).ok().map(|metadata| metadata.ino());
+                    ring_fds.push((fd, inode));
+                }
+            }
+        }
+    }
+    ring_fds.sort_unstable();
+    Ok(ring_fds)
+}
+
+/// Lists the io_uring fds in a process's own fd table, in ascending order
+///
+/// This is what the full scan checks. Unlike process_uses_io_uring it does
+/// not read every task's fd directory, which would cost a directory read
+/// per thread on every process.
+pub(crate) fn own_ring_fds(proc_root: &Path, pid: u32) -> io::Result<Vec<RawFd>> {
+    let fds = collect_ring_fds(&proc_root.join(format!("{}/fd", pid)))?;
+    Ok(fds.into_iter().map(|(fd, _)| fd).collect())
+}
+
+/// Checks whether a single PID holds any io_uring fds
+///
+/// Scans `<proc_root>/<pid>/fd` and the fd directories of the process's
+/// tasks, since threads created without CLONE_FILES have their own fd
+/// table. A task's ring fd is left out when the process's own table holds
+/// the same ring at the same fd number, as it does for every thread that
+/// shares the table. Rings are told apart by inode, which only identifies
+/// a ring on kernels 5.12 and later. Fails if the process's own fd
+/// directory cannot be read.
+pub fn process_uses_io_uring(proc_root: &Path, pid: u32) -> io::Result<ProcessUsage> {
+    let pid_dir = proc_root.join(pid.to_string());
+    let own = collect_ring_fds(&pid_dir.join("fd"))?;
+    let mut ring_fds: Vec<(u32, RawFd)> = own.iter().map(|&(fd, _)| (pid, fd)).collect();
+
+    if let Ok(tasks) = read_dir(pid_dir.join("task")) {
+        for task in tasks.flatten() {
+            let Ok(tid) = task.file_name().to_string_lossy().parse::<u32>() else {
+                continue;
+            };
+            if tid == pid {
+                continue;
+            }
+            // Tasks may exit while being scanned
+            let Ok(task_fds) = collect_ring_fds(&task.path().join("fd")) else {
+                continue;
+            };
+            ring_fds.extend(
+                task_fds
+                    .into_iter()
+                    .filter(|ring| !own.contains(ring))
+                    .map(|(fd, _)| (tid, fd)),
+            );
+        }
+    }
+
+    ring_fds.sort_unstable();
+    Ok(ProcessUsage {
+        uses_io_uring: !ring_fds.is_empty(),
+        ring_fds,
+    })
+}
+
+/// Lists the inodes of the rings a process has mapped, from the
+/// anon_inode:[io_uring] lines of /proc/<pid>/maps
+///
+/// A mapping keeps its ring alive after the fd is closed, so this finds
+/// rings that the fd scan misses.
+pub(crate) fn ring_mapping_inodes(proc_root: &Path, pid: u32) -> io::Result<Vec<u64>> {
+    let reader = BufReader::new(File::open(proc_root.join(format!("{}/maps", pid)))?);
+    let mut inodes = Vec::new();
+    for line in reader.split(b'\n') {
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        if !line.ends_with("anon_inode:[io_uring]") {
+            continue;
+        }
+        // Fields: address, permissions, offset, device, inode, path
+        if let Some(inode) = line.split_whitespace().nth(4).and_then(|v| v.parse().ok()) {
+            if !inodes.contains(&inode) {
+                inodes.push(inode);
+            }
+        }
+    }
+    Ok(inodes)
+}
+
+/// Lists the io_uring fds of a process and its tasks as (TID, fd, ring
+/// inode), so that an fd number reused for a new ring between polls is
+/// still noticed
+pub fn ring_fds_with_inodes(proc_root: &Path, pid: u32) -> io::Result<Vec<(u32, RawFd, Option<u64>)>> {
+    let usage = process_uses_io_uring(proc_root, pid)?;
+    Ok(usage
+        .ring_fds
+        .into_iter()
+        .map(|(tid, fd)| (tid, fd, get_ring_info(proc_root, pid, tid, fd).inode))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An fdinfo file as printed by a 6.x kernel for a ring with 10002
+    /// registered files, the last two of them NVMe namespaces
+    fn fdinfo() -> String {
+        let mut contents = String::from(
+            "pos:\t0\nflags:\t02000002\nmnt_id:\t15\nino:\t2061\nSqMask:\t0x7\nSqHead:\t12\nSqTail:\t12\n\
+             CachedSqHead:\t12\nCqMask:\t0xf\nCqHead:\t12\nCqTail:\t12\nCachedCqTail:\t12\nSQEs:\t0\nCQEs:\t0\n\
+             SqThread:\t-1\nSqThreadCpu:\t-1\nUserFiles:\t10002\n",
+        );
+        for index in 0..10000 {
+            contents.push_str(&format!("{:5}: /srv/data/file-{}\n", index, index));
+        }
+        contents.push_str("10000: /dev/nvme0n1\n10001: /dev/nvme1n1\n");
+        contents.push_str(
+            "UserBufs:\t2\n    0: 0x7f0000000000/4096\n    1: 0x7f0000001000/8192\nPollList:\n\
+             CqOverflowList:\n  user_data=1, res=0, flags=0\n  user_data=2, res=-11, flags=0\n\
+             NAPI:\tenabled\nnapi_busy_poll_dt:\t50000\nnapi_prefer_busy_poll:\ttrue\n",
+        );
+        contents
+    }
+
+    #[test]
+    fn entries_past_index_9999() {
+        let contents = fdinfo();
+        let files = proc_field_entries(&contents, "UserFiles");
+        assert_eq!(files.len(), 10002);
+        assert_eq!(files[0], "/srv/data/file-0");
+        assert_eq!(files[9999], "/srv/data/file-9999");
+        assert_eq!(&files[10000..], ["/dev/nvme0n1", "/dev/nvme1n1"]);
+        assert_eq!(proc_field_entries(&contents, "UserBufs").len(), 2);
+        assert!(proc_field_entries(&contents, "Missing").is_empty());
+    }
+
+    #[test]
+    fn ring_fdinfo() {
+        let ring = parse_ring_fdinfo(5, &fdinfo());
+        assert_eq!(ring.fd, 5);
+        assert_eq!(ring.inode, Some(2061));
+        assert_eq!(ring.sq_entries, Some(8));
+        assert_eq!(ring.cq_entries, Some(16));
+        assert_eq!(ring.sq_thread, Some(-1));
+        assert!(!ring.uses_sqpoll());
+        assert!(!ring.is_probe_ring());
+        assert_eq!(ring.registered_files.len(), 10002);
+        assert_eq!(ring.registered_files.last().map(String::as_str), Some("/dev/nvme1n1"));
+        assert_eq!(ring.registered_buffers, Some(2));
+        assert_eq!(ring.registered_buffer_bytes, Some(12288));
+        assert_eq!(ring.cq_overflow, Some(2));
+    }
+
+    #[test]
+    fn buffer_bytes() {
+        assert_eq!(registered_buffer_bytes("UserBufs:\t0\nPollList:\n"), Some(0));
+        assert_eq!(registered_buffer_bytes("UserBufs:\t1\n    0: 0x1000/bad\n"), None);
+        assert_eq!(registered_buffer_bytes("UserFiles:\t0\n"), None);
+    }
+
+    #[test]
+    fn napi_fdinfo() {
+        let napi = parse_napi_fdinfo(&fdinfo()).expect("NAPI is listed");
+        assert!(napi.enabled);
+        assert_eq!(napi.busy_poll_timeout_us, Some(50));
+        assert_eq!(napi.prefer_busy_poll, Some(true));
+
+        let napi = parse_napi_fdinfo("NAPI:\tdisabled\nnapi_busy_poll_to:\t20\n").expect("NAPI is listed");
+        assert!(!napi.enabled);
+        assert_eq!(napi.busy_poll_timeout_us, Some(20));
+        assert_eq!(napi.prefer_busy_poll, None);
+
+        assert!(parse_napi_fdinfo("SqMask:\t0x7\n").is_none());
+    }
+}
@@ -0,0 +1,587 @@
+//! Scanning every process in /proc for io_uring instances
+
+use std::collections::HashSet;
+use std::fs::read_dir;
+use std::io;
+use std::os::fd::RawFd;
+use std::path::{Path, PathBuf};
+
+#[cfg(all(feature = "bpf", target_os = "linux"))]
+use log::warn;
+use log::{debug, trace};
+use serde::Serialize;
+
+use crate::process::{
+    get_parent_pid, get_process_info, get_process_name, get_process_state, get_tasks, is_io_uring_task_name,
+    MemoryInfo, ProcessInfo,
+};
+use crate::ring::{get_ring_info, ring_mapping_inodes, infer_workload, own_ring_fds, RingInfo};
+use crate::system::{kernel_meets, SystemInfo};
+
+/// Processes with fewer open fds than this are skipped by a fast scan
+pub const FAST_MIN_FDS: usize = 8;
+
+/// Process names (comm prefixes) that are always inspected by a fast scan,
+/// however few fds they hold
+const FAST_ALLOWLIST: &[&str] = &[
+    "postgres", "mysqld", "mariadbd", "qemu", "fio", "nginx", "envoy", "scylla", "redpanda",
+    "tigerbeetle", "java", "node", "bun", "deno",
+];
+
+/// Whether the fast pre-filter skips a process: it has few open fds
+/// and its name is not on the allowlist
+///
+/// Only the fd directory is listed; no fd link is read. This can miss
+/// small programs that hold a ring and little else.
+fn fast_mode_skips(proc_root: &Path, pid: u32) -> bool {
+    let Ok(fds) = read_dir(proc_root.join(format!("{}/fd", pid))) else {
+        // Leave unreadable processes to the full check so they are reported
+        return false;
+    };
+    if fds.take(FAST_MIN_FDS).count() >= FAST_MIN_FDS {
+        return false;
+    }
+    let name = get_process_name(proc_root, pid).unwrap_or_default();
+    !FAST_ALLOWLIST.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// How a process was found to use io_uring
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum DetectionMethod {
+    /// It holds a ring fd but has not mapped any ring
+    Fd,
+    /// It has a ring mapped but holds no ring fd, e.g. because it closed
+    /// the fd after mapping or the fd lives in another process
+    Mapping,
+    /// It holds a ring fd and has a ring mapped, as usual
+    Both,
+}
+
+/// A process found to be using io_uring, with the rings it holds
+#[derive(Debug, Serialize)]
+pub struct Detection {
+    pub pid: u32,
+    pub info: ProcessInfo,
+    pub rings: Vec<RingInfo>,
+    pub detected_via: DetectionMethod,
+    /// Inodes of rings the process has mapped without holding an fd for them
+    pub mapped_ring_inodes: Vec<u64>,
+}
+
+/// Outcome of checking a single process for io_uring
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanStatus {
+    UsesIoUring,
+    Clean,
+    /// The fd directory could not be read
+    Unreadable,
+    /// The fd directory could not be read for lack of permission, as for
+    /// other users' processes when not running as root
+    PermissionDenied,
+    /// The process was a zombie or dead (state Z or X), so an empty or
+    /// unreadable fd directory says nothing about its io_uring usage
+    Exiting,
+    /// Not inspected because of the fast pre-filter
+    Skipped,
+    /// Exited during the scan, before its fds or details could be read, so
+    /// it was left out rather than reported with empty details
+    Vanished,
+}
+
+impl ScanStatus {
+    /// Whether the process uses io_uring, None when it could not be told
+    pub fn uses_io_uring(self) -> Option<bool> {
+        match self {
+            ScanStatus::UsesIoUring => Some(true),
+            ScanStatus::Clean => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Controls which processes and rings a scan reports
+#[derive(Debug, Default, Clone)]
+pub struct ScanOptions {
+    /// Ignore rings with 1-2 SQ entries, which are usually support probes
+    pub hide_probe_rings: bool,
+    /// Leave the calling process out of the scan
+    pub ignore_self: bool,
+    /// Skip processes with few fds that are not on FAST_ALLOWLIST
+    pub fast: bool,
+    /// Only keep processes whose cgroup path contains one of these
+    pub cgroup_filter: Vec<String>,
+    /// Drop processes whose cgroup path contains one of these
+    pub cgroup_exclude: Vec<String>,
+    /// Inspect only this process
+    pub pid: Option<u32>,
+    /// Inspect only processes whose comm contains this, ignoring case
+    pub name: Option<String>,
+    /// Keep only the first process found running each executable
+    pub once_per_exe: bool,
+    /// List every scanned process in the report, not only io_uring users;
+    /// used by report::run_with
+    pub include_all_processes: bool,
+    /// Read processes from `<root>/proc` instead of /proc, e.g. a
+    /// container's procfs bind-mounted on the host
+    pub root: Option<PathBuf>,
+    /// Only inspect the processes a BPF task_file iterator finds holding
+    /// io_uring fds, instead of every process in /proc. Ignored with `root`.
+    #[cfg(feature = "bpf")]
+    pub bpf: bool,
+}
+
+/// The procfs to read: `<root>/proc`, or /proc when no root is given
+pub fn proc_root(root: Option<&Path>) -> PathBuf {
+    root.unwrap_or(Path::new("/")).join("proc")
+}
+
+/// Applies the cgroup filter and exclude lists to a process's cgroup path
+///
+/// A process whose cgroup is unknown never matches a filter, so it is only
+/// reported when no filter is given.
+fn cgroup_selected(cgroup: Option<&str>, options: &ScanOptions) -> bool {
+    let matches = |patterns: &[String]| {
+        cgroup.is_some_and(|cgroup| patterns.iter().any(|pattern| cgroup.contains(pattern.as_str())))
+    };
+    (options.cgroup_filter.is_empty() || matches(&options.cgroup_filter))
+        && !matches(&options.cgroup_exclude)
+}
+
+/// Result of scanning /proc for io_uring users
+#[derive(Debug)]
+pub struct ScanResult {
+    pub detections: Vec<Detection>,
+    /// Every scanned PID with whether it uses io_uring
+    pub inventory: Vec<(u32, ScanStatus)>,
+    /// Detections left out by once_per_exe because an earlier one ran the
+    /// same executable
+    pub suppressed_duplicates: usize,
+}
+
+impl ScanResult {
+    /// Number of processes whose fds could not be read for lack of permission
+    pub fn permission_denied_count(&self) -> usize {
+        self.inventory
+            .iter()
+            .filter(|(_, status)| *status == ScanStatus::PermissionDenied)
+            .count()
+    }
+
+    /// Number of processes that exited during the scan, before they could be read
+    pub fn vanished_count(&self) -> usize {
+        self.inventory
+            .iter()
+            .filter(|(_, status)| *status == ScanStatus::Vanished)
+            .count()
+    }
+}
+
+/// The processes holding io_uring fds according to the BPF iterator, or
+/// None when it is not wanted or not usable and /proc has to be walked
+#[cfg(all(feature = "bpf", target_os = "linux"))]
+fn bpf_pids(options: &ScanOptions) -> Option<Vec<u32>> {
+    if !options.bpf || options.pid.is_some() || options.root.is_some() {
+        return None;
+    }
+    match crate::bpf::io_uring_fds() {
+        Ok(fds) => {
+            let mut pids: Vec<u32> = fds.into_iter().map(|(pid, _)| pid).collect();
+            pids.dedup();
+            debug!("BPF iterator found {} process(es) holding io_uring fds", pids.len());
+            Some(pids)
+        }
+        Err(e) => {
+            warn!("BPF iterator unavailable, scanning /proc instead: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(not(all(feature = "bpf", target_os = "linux")))]
+fn bpf_pids(_options: &ScanOptions) -> Option<Vec<u32>> {
+    None
+}
+
+/// Scans all running processes for io_uring instances without printing
+///
+/// The scan is strictly read-only: it only lists directories and reads
+/// files and symlinks under /proc, and never writes to /proc or any cgroup
+/// file, so it is safe to run against a frozen or quarantined cgroup.
+pub fn scan_processes(options: &ScanOptions) -> io::Result<ScanResult> {
+    let proc_root = &proc_root(options.root.as_deref());
+    let mut detections = Vec::new();
+    let mut inventory = Vec::new();
+    let self_pid = std::process::id();
+    let name_filter = options.name.as_deref().map(str::to_lowercase);
+
+    let pids: Vec<u32> = match (options.pid, bpf_pids(options)) {
+        (Some(pid), _) => vec![pid],
+        (None, Some(pids)) => pids,
+        (None, None) => read_dir(proc_root)?
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_string_lossy().parse().ok())
+            .collect(),
+    };
+
+    for pid in pids {
+        if options.ignore_self && pid == self_pid {
+            continue;
+        }
+        if let Some(filter) = &name_filter {
+            let name = get_process_name(proc_root, pid).unwrap_or_default();
+            if !name.to_lowercase().contains(filter.as_str()) {
+                continue;
+            }
+        }
+        if options.fast && fast_mode_skips(proc_root, pid) {
+            trace!("PID {}: skipped by the fast pre-filter", pid);
+            inventory.push((pid, ScanStatus::Skipped));
+            continue;
+        }
+        let ring_fds = own_ring_fds(proc_root, pid);
+        let mapped_inodes = ring_mapping_inodes(proc_root, pid).unwrap_or_default();
+        if mapped_inodes.is_empty() && !ring_fds.as_ref().is_ok_and(|fds| !fds.is_empty()) {
+            let status = match &ring_fds {
+                _ if matches!(get_process_state(proc_root, pid), Some('Z' | 'X')) => ScanStatus::Exiting,
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => ScanStatus::PermissionDenied,
+                // A live process always has an fd directory
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    debug!("PID {}: exited before its fds could be read", pid);
+                    ScanStatus::Vanished
+                }
+                Err(e) => {
+                    debug!("PID {}: could not read fds: {}", pid, e);
+                    ScanStatus::Unreadable
+                }
+                Ok(_) => ScanStatus::Clean,
+            };
+            inventory.push((pid, status));
+            continue;
+        }
+        let ring_fds = ring_fds.unwrap_or_default();
+
+        let all_rings: Vec<RingInfo> = ring_fds.iter().map(|fd| get_ring_info(proc_root, pid, pid, *fd)).collect();
+        let mapped_ring_inodes: Vec<u64> = mapped_inodes
+            .iter()
+            .copied()
+            .filter(|inode| !all_rings.iter().any(|ring| ring.inode == Some(*inode)))
+            .collect();
+        let mut rings: Vec<RingInfo> = all_rings
+            .into_iter()
+            .filter(|ring| !(options.hide_probe_rings && ring.is_probe_ring()))
+            .collect();
+
+        if rings.is_empty() && mapped_ring_inodes.is_empty() {
+            inventory.push((pid, ScanStatus::Clean));
+            continue;
+        }
+
+        // Every read in get_process_info fails quietly once the process
+        // has gone, so check it still exists after reading it
+        let info = get_process_info(proc_root, pid);
+        if !proc_root.join(pid.to_string()).exists() {
+            debug!("PID {}: exited while being scanned", pid);
+            inventory.push((pid, ScanStatus::Vanished));
+            continue;
+        }
+        inventory.push((pid, ScanStatus::UsesIoUring));
+
+        if !cgroup_selected(info.cgroup.as_deref(), options) {
+            continue;
+        }
+
+        // Rings without registered files are judged by the process's fds
+        let root = proc_root.join(format!("{}/root", pid));
+        for ring in &mut rings {
+            ring.inferred_workload = if ring.registered_files.is_empty() {
+                infer_workload(&root, info.open_fds.iter().map(|(_, target)| target.as_str()))
+            } else {
+                infer_workload(&root, ring.registered_files.iter().map(String::as_str))
+            };
+        }
+
+        let detected_via = match (rings.is_empty(), mapped_inodes.is_empty()) {
+            (false, true) => DetectionMethod::Fd,
+            (true, _) => DetectionMethod::Mapping,
+            (false, false) => DetectionMethod::Both,
+        };
+        detections.push(Detection { pid, info, rings, detected_via, mapped_ring_inodes });
+    }
+
+    let suppressed_duplicates = if options.once_per_exe { keep_first_per_exe(&mut detections) } else { 0 };
+    Ok(ScanResult { detections, inventory, suppressed_duplicates })
+}
+
+/// Drops every detection whose executable was already seen in an earlier
+/// one, returning how many were dropped
+///
+/// Processes without a readable exe path are never treated as duplicates.
+fn keep_first_per_exe(detections: &mut Vec<Detection>) -> usize {
+    let before = detections.len();
+    let mut seen_exes = HashSet::new();
+    detections.retain(|detection| match &detection.info.exe_path {
+        Some(exe) => seen_exes.insert(exe.clone()),
+        None => true,
+    });
+    before - detections.len()
+}
+
+/// An io_uring SQPOLL or io-wq worker thread
+#[derive(Debug, Clone, Serialize)]
+pub struct IoUringThread {
+    /// Process the thread belongs to
+    pub pid: u32,
+    pub tid: u32,
+    /// Thread name: iou-sqp-<pid> for SQPOLL, iou-wrk-<tid> for io-wq workers
+    pub name: String,
+}
+
+/// Finds io_uring SQPOLL (iou-sqp-*) and io-wq worker (iou-wrk-*) threads
+///
+/// Since kernel 5.12 these run as threads of the process that owns the
+/// ring, so each is found under <proc_root>/<pid>/task and attributed to <pid>.
+/// They perform I/O without the process making syscalls.
+pub fn find_sqpoll_threads(proc_root: &Path) -> Vec<IoUringThread> {
+    let mut threads = Vec::new();
+    let Ok(entries) = read_dir(proc_root) else {
+        return threads;
+    };
+
+    for entry in entries.flatten() {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        for (tid, name) in get_tasks(proc_root, pid) {
+            if is_io_uring_task_name(&name) {
+                threads.push(IoUringThread { pid, tid, name });
+            }
+        }
+    }
+
+    threads.sort_by_key(|thread| thread.pid);
+    threads
+}
+
+/// Sums the memory usage of all detected processes
+pub fn total_memory(detections: &[Detection]) -> MemoryInfo {
+    let mut total = MemoryInfo::default();
+    for memory in detections.iter().filter_map(|d| d.info.memory_status.as_ref()) {
+        if let Some(vm) = memory.virtual_memory {
+            *total.virtual_memory.get_or_insert(0) += vm;
+        }
+        if let Some(rss) = memory.resident_memory {
+            *total.resident_memory.get_or_insert(0) += rss;
+        }
+    }
+    total
+}
+
+/// Whether `ancestor` appears in the parent chain of `pid`
+fn is_ancestor(proc_root: &Path, ancestor: u32, pid: u32) -> bool {
+    let mut current = pid;
+    // Bounded walk in case the chain changes while it is being read
+    for _ in 0..256 {
+        match get_parent_pid(proc_root, current) {
+            Some(parent) if parent == ancestor => return true,
+            Some(parent) if parent > 1 => current = parent,
+            _ => return false,
+        }
+    }
+    false
+}
+
+/// Whether two processes could have inherited the same ring through fork:
+/// one descends from the other, or they are siblings below a common
+/// parent other than init
+fn processes_related(proc_root: &Path, a: &Detection, b: &Detection) -> bool {
+    if is_ancestor(proc_root, a.pid, b.pid) || is_ancestor(proc_root, b.pid, a.pid) {
+        return true;
+    }
+    matches!((a.info.ppid, b.info.ppid), (Some(pa), Some(pb)) if pa == pb && pa > 1)
+}
+
+/// Groups the detected rings by inode, with the processes and fds holding each
+pub fn group_rings_by_inode(detections: &[Detection]) -> Vec<(u64, Vec<(&Detection, RawFd)>)> {
+    let mut by_inode: Vec<(u64, Vec<(&Detection, RawFd)>)> = Vec::new();
+    for detection in detections {
+        for ring in &detection.rings {
+            let Some(inode) = ring.inode else {
+                continue;
+            };
+            match by_inode.iter_mut().find(|(existing, _)| *existing == inode) {
+                Some((_, holders)) => holders.push((detection, ring.fd)),
+                None => by_inode.push((inode, vec![(detection, ring.fd)])),
+            }
+        }
+    }
+    by_inode
+}
+
+/// Whether any two holders of a ring are distinct processes that cannot
+/// have inherited it from one another
+pub fn holders_unrelated(proc_root: &Path, holders: &[(&Detection, RawFd)]) -> bool {
+    holders.iter().enumerate().any(|(i, (a, _))| {
+        holders[i + 1..]
+            .iter()
+            .any(|(b, _)| a.pid != b.pid && !processes_related(proc_root, a, b))
+    })
+}
+
+/// Whether ring inodes identify individual rings, which is the case from
+/// kernel 5.12; earlier kernels share one anonymous inode for all rings
+pub fn ring_inodes_unique(sys_info: &SystemInfo) -> bool {
+    kernel_meets(&sys_info.kernel_version, (5, 12))
+}
+
+/// Whether the process runs from a deleted file or a memfd
+pub fn runs_fileless(info: &ProcessInfo) -> bool {
+    info.exe_is_memory_backed
+}
+
+/// Whether a detection warrants a high-risk alert: a fileless executable
+/// (deleted on disk or memfd-backed) or a ring with direct device access
+pub fn is_high_risk(detection: &Detection) -> bool {
+    runs_fileless(&detection.info) || detection.rings.iter().any(|ring| !ring.backing_devices.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::time::SystemTime;
+
+    /// Every entry below `dir` with its contents (or link target) and
+    /// modification time
+    fn snapshot(dir: &Path, entries: &mut Vec<(PathBuf, Vec<u8>, SystemTime)>) {
+        for entry in read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            let metadata = std::fs::symlink_metadata(&path).unwrap();
+            let contents = if metadata.is_symlink() {
+                std::fs::read_link(&path).unwrap().into_os_string().into_encoded_bytes()
+            } else if metadata.is_dir() {
+                snapshot(&path, entries);
+                Vec::new()
+            } else {
+                std::fs::read(&path).unwrap()
+            };
+            entries.push((path, contents, metadata.modified().unwrap()));
+        }
+    }
+
+    /// Sets the mode of every file and directory below `dir`, directories
+    /// after their contents so that a read-only tree can still be walked
+    fn set_modes(dir: &Path, file_mode: u32, dir_mode: u32) {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(dir_mode | 0o700)).unwrap();
+        for entry in read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            let metadata = std::fs::symlink_metadata(&path).unwrap();
+            if metadata.is_dir() {
+                set_modes(&path, file_mode, dir_mode);
+            } else if !metadata.is_symlink() {
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(file_mode)).unwrap();
+            }
+        }
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(dir_mode)).unwrap();
+    }
+
+    #[test]
+    fn scan_leaves_read_only_proc_untouched() {
+        // A process holding a ring, in a proc root made read-only as a
+        // frozen cgroup's would be treated; the scan must still find the
+        // ring and must not change any file
+        let root = std::env::temp_dir().join(format!("io_uring_detector-readonly-{}", std::process::id()));
+        let proc = root.join("proc");
+        let pid_dir = proc.join("4242");
+        create_dir_all(pid_dir.join("fd")).unwrap();
+        create_dir_all(pid_dir.join("fdinfo")).unwrap();
+        create_dir_all(pid_dir.join("task/4242")).unwrap();
+        write(pid_dir.join("comm"), "frozen\n").unwrap();
+        write(pid_dir.join("task/4242/comm"), "frozen\n").unwrap();
+        write(pid_dir.join("stat"), "4242 (frozen) S 1 4242 4242 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0 100 0 0").unwrap();
+        write(pid_dir.join("status"), "Name:\tfrozen\nPPid:\t1\nUid:\t0\t0\t0\t0\nVmRSS:\t 1024 kB\n").unwrap();
+        write(pid_dir.join("cmdline"), "frozen\0").unwrap();
+        write(pid_dir.join("cgroup"), "0::/quarantine.slice\n").unwrap();
+        write(pid_dir.join("maps"), "").unwrap();
+        write(pid_dir.join("fdinfo/5"), "pos:\t0\nflags:\t02000002\nino:\t777\nSqMask:\t0x7\nCqMask:\t0xf\n").unwrap();
+        symlink("anon_inode:[io_uring]", pid_dir.join("fd/5")).unwrap();
+        write(proc.join("uptime"), "1000.00 500.00\n").unwrap();
+
+        let mut before = Vec::new();
+        snapshot(&root, &mut before);
+        set_modes(&root, 0o444, 0o555);
+
+        let options = ScanOptions { root: Some(root.clone()), ..Default::default() };
+        let result = scan_processes(&options);
+
+        set_modes(&root, 0o644, 0o755);
+        let mut after = Vec::new();
+        snapshot(&root, &mut after);
+        remove_dir_all(&root).unwrap();
+
+        let result = result.expect("a read-only proc root must be scannable");
+        assert_eq!(result.inventory, vec![(4242, ScanStatus::UsesIoUring)]);
+        assert_eq!(result.detections[0].rings[0].sq_entries, Some(8));
+        assert!(before == after, "the scan modified the proc root");
+    }
+
+    #[test]
+    fn process_without_fd_directory_is_vanished() {
+        // As seen when a process exits between the /proc listing and the
+        // fd read: the pid directory is listed but has no fd directory
+        let root = std::env::temp_dir().join(format!("io_uring_detector-vanished-{}", std::process::id()));
+        let pid_dir = root.join("proc/4242");
+        create_dir_all(&pid_dir).unwrap();
+        write(pid_dir.join("comm"), "short-lived\n").unwrap();
+
+        let options = ScanOptions { root: Some(root.clone()), ..Default::default() };
+        let result = scan_processes(&options);
+        remove_dir_all(&root).unwrap();
+
+        let result = result.expect("a vanished process must not fail the scan");
+        assert_eq!(result.inventory, vec![(4242, ScanStatus::Vanished)]);
+        assert_eq!(result.vanished_count(), 1);
+        assert!(result.detections.is_empty());
+    }
+
+    #[test]
+    fn once_per_exe_keeps_first_process_of_each_executable() {
+        // Two fio processes and one qemu process, each holding a ring
+        let root = std::env::temp_dir().join(format!("io_uring_detector-once-per-exe-{}", std::process::id()));
+        for (pid, exe) in [(4242, "/usr/bin/fio"), (4243, "/usr/bin/fio"), (4244, "/usr/bin/qemu")] {
+            let pid_dir = root.join(format!("proc/{}", pid));
+            create_dir_all(pid_dir.join("fd")).unwrap();
+            create_dir_all(pid_dir.join("fdinfo")).unwrap();
+            write(pid_dir.join("comm"), "worker\n").unwrap();
+            write(pid_dir.join("status"), "Name:\tworker\nPPid:\t1\nUid:\t0\t0\t0\t0\n").unwrap();
+            write(pid_dir.join("maps"), "").unwrap();
+            write(pid_dir.join("fdinfo/5"), "ino:\t777\nSqMask:\t0x7\nCqMask:\t0xf\n").unwrap();
+            symlink(exe, pid_dir.join("exe")).unwrap();
+            symlink("anon_inode:[io_uring]", pid_dir.join("fd/5")).unwrap();
+        }
+
+        let scan = |once_per_exe| {
+            let options = ScanOptions { root: Some(root.clone()), once_per_exe, ..Default::default() };
+            scan_processes(&options).expect("the fake proc root is readable")
+        };
+        let all = scan(false);
+        let once = scan(true);
+        remove_dir_all(&root).unwrap();
+
+        // Processes are visited in directory order, so which fio comes
+        // first is not fixed
+        let pids = |result: &ScanResult| {
+            let mut pids: Vec<u32> = result.detections.iter().map(|detection| detection.pid).collect();
+            pids.sort_unstable();
+            pids
+        };
+        assert_eq!(pids(&all), vec![4242, 4243, 4244]);
+        assert_eq!(all.suppressed_duplicates, 0);
+        let kept = pids(&once);
+        assert_eq!(kept.len(), 2);
+        assert!(kept[0] == 4242 || kept[0] == 4243);
+        assert_eq!(kept[1], 4244);
+        assert_eq!(once.suppressed_duplicates, 1);
+    }
+}
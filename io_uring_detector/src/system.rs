@@ -0,0 +1,483 @@
+//! Host information relevant to io_uring: kernel version and config,
+//! architecture, sysctl, seccomp and cgroup state
+
+use std::fs::{read_to_string, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+#[cfg(target_os = "linux")]
+use libc::{uname, utsname};
+
+use crate::probe::UringStatus;
+use crate::process::{proc_field, read_proc_file, serialize_path_lossy};
+use crate::ring::resolve_in_root;
+
+/// Kernel build options relevant to io_uring
+pub const IO_URING_CONFIG_OPTIONS: &[&str] = &["CONFIG_IO_URING", "CONFIG_IO_WQ"];
+
+/// Serializes kernel config options as a name to value map
+fn serialize_config_options<S: serde::Serializer>(
+    options: &[(&'static str, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(options.iter().map(|(name, value)| (name, value)))
+}
+
+/// Structure to hold the io_uring related kernel build configuration
+#[derive(Debug, Default, Serialize)]
+pub struct KernelConfig {
+    #[serde(serialize_with = "serialize_path_lossy")]
+    pub source: PathBuf,
+    #[serde(serialize_with = "serialize_config_options")]
+    pub options: Vec<(&'static str, String)>,
+}
+
+impl KernelConfig {
+    /// Returns the configured value of an option: "n" when the config marks
+    /// it as not set, None when the option does not appear in the config
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(option, _)| *option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether the kernel was built with io_uring support
+    pub fn io_uring_enabled(&self) -> bool {
+        self.value("CONFIG_IO_URING") == Some("y")
+    }
+}
+
+/// Structure to hold system information
+#[derive(Debug, Serialize)]
+pub struct SystemInfo {
+    pub architecture: String,
+    pub kernel_version: String,
+    /// Release string from /proc/version, which uname overrides do not touch
+    pub proc_version: Option<String>,
+    pub hostname: String,
+    pub io_uring_support: bool,
+    pub min_kernel_version_met: bool,
+    /// Authoritative build-time answer, when the kernel config is readable
+    pub kernel_config: Option<KernelConfig>,
+    /// Stable host identifier that survives hostname changes
+    pub machine_id: Option<String>,
+    /// Whether cgroup v2 io.max limits apply to the detector's cgroup,
+    /// None without a readable cgroup v2 hierarchy
+    pub cgroup_io_limited: Option<bool>,
+    /// kernel.io_uring_disabled: 0 allows everyone, 1 restricts io_uring to
+    /// CAP_SYS_ADMIN and kernel.io_uring_group, 2 disables it; None before 6.6
+    pub io_uring_disabled: Option<u8>,
+    /// Why io_uring is or is not available, set once the probe has run
+    pub reason_code: SupportReason,
+}
+
+/// Machine-readable reason for the io_uring support verdict
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SupportReason {
+    /// io_uring_setup succeeded
+    Ok,
+    /// The kernel predates io_uring (5.1)
+    KernelTooOld,
+    /// io_uring_setup is not implemented
+    Enosys,
+    /// kernel.io_uring_disabled is set to 2
+    DisabledSysctl,
+    /// A seccomp filter is active and the syscall was rejected
+    BlockedSeccomp,
+    /// Denied with no sysctl or seccomp explanation, e.g. by SELinux
+    BlockedLsm,
+    /// kernel.io_uring_disabled is 1 and the detector lacks CAP_SYS_ADMIN
+    NoPermission,
+    /// io_uring_setup exists but ran out of locked memory or fds
+    ResourceLimited,
+    #[default]
+    UnknownError,
+}
+
+impl Default for SystemInfo {
+    fn default() -> Self {
+        SystemInfo {
+            architecture: String::from("unknown"),
+            kernel_version: String::from("unknown"),
+            proc_version: None,
+            hostname: String::from("unknown"),
+            io_uring_support: false,
+            min_kernel_version_met: false,
+            kernel_config: None,
+            machine_id: None,
+            cgroup_io_limited: None,
+            io_uring_disabled: None,
+            reason_code: SupportReason::UnknownError,
+        }
+    }
+}
+
+impl SystemInfo {
+    /// Whether uname and /proc/version disagree on the major.minor version
+    pub fn kernel_version_mismatch(&self) -> bool {
+        match &self.proc_version {
+            Some(proc_version) => {
+                parse_kernel_version(proc_version) != parse_kernel_version(&self.kernel_version)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Reads the kernel release from /proc/version ("Linux version <release> ...")
+fn read_proc_version() -> Option<String> {
+    let contents = read_to_string("/proc/version").ok()?;
+    let release = contents.strip_prefix("Linux version ")?.split_whitespace().next()?;
+    Some(release.to_string())
+}
+
+/// Whether an io.max file sets any bandwidth or IOPS limit
+fn io_max_has_limits(contents: &str) -> bool {
+    contents.lines().any(|line| {
+        line.split_whitespace()
+            .skip(1)
+            .any(|limit| limit.split_once('=').is_some_and(|(_, value)| value != "max"))
+    })
+}
+
+/// Checks the io.max files of the detector's cgroup v2 and its ancestors,
+/// since limits set on a parent apply to everything below it
+fn read_cgroup_io_limited() -> Option<bool> {
+    let cgroups = read_proc_file("/proc/self/cgroup").ok()?;
+    let cgroup = cgroups.lines().find_map(|line| line.strip_prefix("0::"))?;
+
+    // Hybrid hierarchies mount cgroup v2 below the v1 controllers
+    let mount = ["/sys/fs/cgroup", "/sys/fs/cgroup/unified"]
+        .into_iter()
+        .map(Path::new)
+        .find(|mount| mount.join("cgroup.controllers").exists())?;
+
+    let mut dir = resolve_in_root(mount, Path::new(cgroup));
+    loop {
+        if let Ok(io_max) = read_to_string(dir.join("io.max")) {
+            if io_max_has_limits(&io_max) {
+                return Some(true);
+            }
+        }
+        if dir == mount || !dir.pop() {
+            return Some(false);
+        }
+    }
+}
+
+/// Reads the machine ID from /etc/machine-id or /var/lib/dbus/machine-id
+fn read_machine_id() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .filter_map(|path| read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .find(|id| !id.is_empty())
+}
+
+/// Reads the kernel config from /proc/config.gz or /boot/config-<release>
+fn read_kernel_config(release: &str) -> Option<KernelConfig> {
+    read_first_kernel_config(&[
+        PathBuf::from("/proc/config.gz"),
+        PathBuf::from(format!("/boot/config-{}", release)),
+    ])
+}
+
+/// Reads the first of the given kernel configs that can be read, moving on
+/// when a file is missing, unreadable or, for .gz files, fails to decompress
+fn read_first_kernel_config(paths: &[PathBuf]) -> Option<KernelConfig> {
+    paths.iter().find_map(|path| {
+        let contents = if path.extension().is_some_and(|extension| extension == "gz") {
+            let mut contents = String::new();
+            flate2::read::GzDecoder::new(File::open(path).ok()?)
+                .read_to_string(&mut contents)
+                .ok()?;
+            contents
+        } else {
+            read_to_string(path).ok()?
+        };
+        Some(parse_kernel_config(path.clone(), &contents))
+    })
+}
+
+/// Extracts the io_uring related options from kernel config contents
+fn parse_kernel_config(source: PathBuf, contents: &str) -> KernelConfig {
+    let mut config = KernelConfig {
+        source,
+        options: Vec::new(),
+    };
+
+    for name in IO_URING_CONFIG_OPTIONS {
+        let set_prefix = format!("{}=", name);
+        let unset_line = format!("# {} is not set", name);
+
+        for line in contents.lines() {
+            if let Some(value) = line.strip_prefix(&set_prefix) {
+                config.options.push((name, value.trim().to_string()));
+                break;
+            } else if line.trim() == unset_line {
+                config.options.push((name, String::from("n")));
+                break;
+            }
+        }
+    }
+
+    config
+}
+
+/// Maps the architecture names reported by uname and other tools to one
+/// canonical spelling per architecture, following the kernel's own names
+/// (e.g. `amd64` becomes `x86_64`, `arm64` becomes `aarch64`). Unknown
+/// names are passed through and an empty name is reported as `unknown`.
+fn normalize_architecture(machine: &str) -> String {
+    let machine = machine.trim();
+    let canonical = match machine {
+        "" => "unknown",
+        "amd64" | "x64" | "x86-64" => "x86_64",
+        "arm64" | "armv8" => "aarch64",
+        // Every 32-bit ARM userland, including armv8l (32-bit on an ARMv8
+        // core), shares the kernel's arch/arm name
+        "armhf" | "armel" | "armv5tel" | "armv6l" | "armv7" | "armv7l" | "armv8l" => "arm",
+        "i386" | "i486" | "i586" | "i686" | "x86" => "i686",
+        "ppc64el" => "ppc64le",
+        "riscv64gc" => "riscv64",
+        other => other,
+    };
+    canonical.to_string()
+}
+
+/// Parses the major and minor version from a kernel release string
+///
+/// Anything after the first '-' or '+' (build suffixes such as
+/// "-126-generic", "-rc4" or the "+" of a dirty tree) is ignored, and a
+/// missing minor version is taken as 0.
+pub fn parse_kernel_version(release: &str) -> Option<(u32, u32)> {
+    let version = release.split(['-', '+']).next().unwrap_or_default();
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = match parts.next() {
+        Some(minor) => minor.parse().ok()?,
+        None => 0,
+    };
+    Some((major, minor))
+}
+
+/// Whether a kernel release string names a release candidate, e.g. 6.8.0-rc4
+fn is_release_candidate(release: &str) -> bool {
+    release
+        .split_once('-')
+        .and_then(|(_, suffix)| suffix.strip_prefix("rc"))
+        .is_some_and(|number| number.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Oldest kernel release with io_uring
+pub const MIN_KERNEL_VERSION: (u32, u32) = (5, 1);
+
+/// Whether a kernel release is at least major.minor
+///
+/// A release candidate of exactly major.minor comes before that release
+/// and does not count.
+pub fn kernel_meets(release: &str, minimum: (u32, u32)) -> bool {
+    match parse_kernel_version(release) {
+        Some(version) if version == minimum => !is_release_candidate(release),
+        Some(version) => version > minimum,
+        None => false,
+    }
+}
+
+/// Get system information including architecture and kernel version
+pub fn system_info() -> io::Result<SystemInfo> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut uts = unsafe { std::mem::zeroed::<utsname>() };
+        if unsafe { uname(&mut uts) } == 0 {
+            let arch = normalize_architecture(
+                &unsafe { std::ffi::CStr::from_ptr(uts.machine.as_ptr()) }.to_string_lossy(),
+            );
+            let kernel = unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) }
+                .to_string_lossy()
+                .into_owned();
+            let hostname = unsafe { std::ffi::CStr::from_ptr(uts.nodename.as_ptr()) }
+                .to_string_lossy()
+                .into_owned();
+            
+            // Check if kernel version meets minimum requirement (5.1 or higher)
+            let min_version_met = kernel_meets(&kernel, MIN_KERNEL_VERSION);
+
+            let kernel_config = read_kernel_config(&kernel);
+
+            Ok(SystemInfo {
+                architecture: arch,
+                kernel_version: kernel,
+                proc_version: read_proc_version(),
+                hostname,
+                io_uring_support: false, // Will be set later
+                min_kernel_version_met: min_version_met,
+                kernel_config,
+                machine_id: read_machine_id(),
+                cgroup_io_limited: read_cgroup_io_limited(),
+                io_uring_disabled: read_io_uring_sysctl(),
+                reason_code: SupportReason::UnknownError, // Will be set later
+            })
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Ok(SystemInfo::default())
+    }
+}
+
+/// Reads the kernel.io_uring_disabled sysctl (kernels 6.6 and later)
+pub fn read_io_uring_sysctl() -> Option<u8> {
+    read_to_string("/proc/sys/kernel/io_uring_disabled")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Whether the detector runs under a seccomp filter (Seccomp: 2)
+fn seccomp_filter_active() -> bool {
+    read_proc_file("/proc/self/status")
+        .ok()
+        .is_some_and(|status| proc_field(&status, "Seccomp") == Some("2"))
+}
+
+/// Works out why the support probe came out the way it did, using the
+/// sysctl and seccomp state to explain failures
+pub fn support_reason(probe: &UringStatus, sys_info: &SystemInfo) -> SupportReason {
+    match probe {
+        UringStatus::Supported(_) => SupportReason::Ok,
+        UringStatus::NotImplemented if !kernel_meets(&sys_info.kernel_version, MIN_KERNEL_VERSION) => {
+            SupportReason::KernelTooOld
+        }
+        // Seccomp filters commonly reject unwanted syscalls with ENOSYS
+        UringStatus::NotImplemented if seccomp_filter_active() => SupportReason::BlockedSeccomp,
+        UringStatus::NotImplemented => SupportReason::Enosys,
+        UringStatus::Blocked => match sys_info.io_uring_disabled {
+            Some(2) => SupportReason::DisabledSysctl,
+            Some(1) if has_cap_sys_admin() == Some(false) => SupportReason::NoPermission,
+            _ if seccomp_filter_active() => SupportReason::BlockedSeccomp,
+            _ => SupportReason::BlockedLsm,
+        },
+        UringStatus::ResourceLimited(_) => SupportReason::ResourceLimited,
+        UringStatus::Error(_) => SupportReason::UnknownError,
+    }
+}
+
+/// CAP_SYS_ADMIN bit in the capability sets of /proc/<pid>/status
+const CAP_SYS_ADMIN: u32 = 21;
+
+/// Whether the detector's effective capability set includes CAP_SYS_ADMIN,
+/// None if CapEff cannot be read
+pub fn has_cap_sys_admin() -> Option<bool> {
+    let status = read_proc_file("/proc/self/status").ok()?;
+    let cap_eff = u64::from_str_radix(proc_field(&status, "CapEff")?, 16).ok()?;
+    Some(cap_eff & (1 << CAP_SYS_ADMIN) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_release_strings() {
+        let cases = [
+            ("5.1.0", Some((5, 1)), true),
+            ("5.0.99", Some((5, 0)), false),
+            ("6.8.0-rc4", Some((6, 8)), true),
+            ("5.1.0-rc1", Some((5, 1)), false),
+            ("5.1.0-rcfoo", Some((5, 1)), true),
+            ("4.19.0-amd64", Some((4, 19)), false),
+            ("5.15.0-126-generic", Some((5, 15)), true),
+            ("6.1.0+", Some((6, 1)), true),
+            ("6.1+", Some((6, 1)), true),
+            ("5.x.0", None, false),
+            ("6", Some((6, 0)), true),
+            ("garbage", None, false),
+            ("", None, false),
+        ];
+        for (release, version, meets) in cases {
+            assert_eq!(parse_kernel_version(release), version, "{}", release);
+            assert_eq!(kernel_meets(release, MIN_KERNEL_VERSION), meets, "{}", release);
+        }
+    }
+
+    #[test]
+    fn architecture_names() {
+        let cases = [
+            ("x86_64", "x86_64"),
+            ("amd64", "x86_64"),
+            ("i686", "i686"),
+            ("i386", "i686"),
+            ("aarch64", "aarch64"),
+            ("arm64", "aarch64"),
+            ("ppc64le", "ppc64le"),
+            ("ppc64el", "ppc64le"),
+            ("s390x", "s390x"),
+            ("riscv64", "riscv64"),
+            ("riscv64gc", "riscv64"),
+            (" x86_64\n", "x86_64"),
+            ("", "unknown"),
+            ("mips64", "mips64"),
+        ];
+        for (machine, canonical) in cases {
+            assert_eq!(normalize_architecture(machine), canonical, "{:?}", machine);
+        }
+    }
+
+    #[test]
+    fn arm32_aliases_share_one_name() {
+        for machine in ["arm", "armhf", "armel", "armv5tel", "armv6l", "armv7", "armv7l", "armv8l"] {
+            assert_eq!(normalize_architecture(machine), "arm", "{:?}", machine);
+        }
+    }
+
+    #[test]
+    fn release_candidate_boundary() {
+        assert!(is_release_candidate("6.8.0-rc4"));
+        assert!(is_release_candidate("5.1.0-rc1"));
+        assert!(!is_release_candidate("5.1.0"));
+        assert!(!is_release_candidate("5.1.0-rc"));
+        assert!(!is_release_candidate("5.1.0-rcfoo"));
+        assert!(!is_release_candidate("4.19.0-amd64"));
+    }
+
+    #[test]
+    fn kernel_config_values() {
+        let config = parse_kernel_config(PathBuf::from("config"), "CONFIG_IO_URING=y\n# CONFIG_IO_WQ is not set\n");
+        assert_eq!(config.value("CONFIG_IO_URING"), Some("y"));
+        assert_eq!(config.value("CONFIG_IO_WQ"), Some("n"));
+        assert!(config.io_uring_enabled());
+
+        let config = parse_kernel_config(PathBuf::from("config"), "CONFIG_IO_WQ=y\n");
+        assert_eq!(config.value("CONFIG_IO_URING"), None);
+        assert!(!config.io_uring_enabled());
+    }
+
+    #[test]
+    fn kernel_config_falls_back_when_gzip_is_corrupt() {
+        let dir = std::env::temp_dir().join(format!("io_uring_detector-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let compressed = dir.join("config.gz");
+        let plain = dir.join("config-6.8.0");
+        std::fs::write(&compressed, b"not gzip data").unwrap();
+        std::fs::write(&plain, "CONFIG_IO_URING=y\n").unwrap();
+
+        let config = read_first_kernel_config(&[compressed.clone(), plain.clone()]);
+        let missing = read_first_kernel_config(&[dir.join("missing.gz"), plain.clone()]);
+        let none = read_first_kernel_config(&[compressed, dir.join("missing")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let config = config.expect("the plain config should be read");
+        assert_eq!(config.source, plain);
+        assert!(config.io_uring_enabled());
+        assert_eq!(missing.map(|config| config.source), Some(plain));
+        assert!(none.is_none());
+    }
+}
@@ -0,0 +1,49 @@
+//! Checking a single PID for io_uring fds through the public API
+
+#![cfg(target_os = "linux")]
+
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::os::unix::fs::symlink;
+
+use io_uring_detector::ring::ring_fds_with_inodes;
+use io_uring_detector::{process_uses_io_uring, ProcessUsage};
+
+#[test]
+fn finds_ring_fds_of_process_and_tasks() {
+    // A fake procfs: fd 5 is a ring, fd 0 is not. Threads 4243 and 4244
+    // have fd tables of their own, each holding a different ring at fd 7,
+    // and thread 4245 shares the process's table
+    let root = std::env::temp_dir().join(format!("io_uring_detector-usage-{}", std::process::id()));
+    let pid_dir = root.join("4242");
+    create_dir_all(pid_dir.join("fd")).unwrap();
+    create_dir_all(pid_dir.join("fdinfo")).unwrap();
+    create_dir_all(pid_dir.join("task/4242/fd")).unwrap();
+    symlink("/dev/null", pid_dir.join("fd/0")).unwrap();
+    symlink("anon_inode:[io_uring]", pid_dir.join("fd/5")).unwrap();
+    write(pid_dir.join("fdinfo/5"), "ino:\t800\n").unwrap();
+    for (tid, inode) in [(4243, 801), (4244, 802)] {
+        let task_dir = pid_dir.join(format!("task/{}", tid));
+        create_dir_all(task_dir.join("fd")).unwrap();
+        create_dir_all(task_dir.join("fdinfo")).unwrap();
+        symlink("anon_inode:[io_uring]", task_dir.join("fd/7")).unwrap();
+        write(task_dir.join("fdinfo/7"), format!("ino:\t{}\n", inode)).unwrap();
+    }
+    create_dir_all(pid_dir.join("task/4245/fd")).unwrap();
+    symlink("/dev/null", pid_dir.join("task/4245/fd/0")).unwrap();
+    symlink("anon_inode:[io_uring]", pid_dir.join("task/4245/fd/5")).unwrap();
+
+    let usage = process_uses_io_uring(&root, 4242);
+    let with_inodes = ring_fds_with_inodes(&root, 4242);
+    let missing = process_uses_io_uring(&root, 4246);
+    remove_dir_all(&root).unwrap();
+
+    let ProcessUsage { uses_io_uring, ring_fds } = usage.expect("the fd directory is readable");
+    assert!(uses_io_uring);
+    assert_eq!(ring_fds, vec![(4242, 5), (4243, 7), (4244, 7)]);
+    // Each task's fdinfo is read from its own fd table
+    assert_eq!(
+        with_inodes.expect("the fd directory is readable"),
+        vec![(4242, 5, Some(800)), (4243, 7, Some(801)), (4244, 7, Some(802))]
+    );
+    assert!(missing.is_err(), "a PID without an fd directory must be an error");
+}
@@ -0,0 +1,44 @@
+//! Detection of a ring held by the test process itself
+
+#![cfg(target_os = "linux")]
+
+use std::io;
+use std::os::fd::RawFd;
+
+use io_uring_detector::{detect_io_uring_support, scan_processes, IoUringParams, ScanOptions, UringStatus};
+
+#[test]
+fn detects_own_ring() {
+    if !matches!(detect_io_uring_support(), UringStatus::Supported(_)) {
+        eprintln!("io_uring is unavailable, skipping");
+        return;
+    }
+
+    let mut params = IoUringParams::default();
+    let ret = unsafe { libc::syscall(libc::SYS_io_uring_setup, 8 as libc::c_uint, &mut params as *mut IoUringParams) };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        // e.g. kernel.io_uring_disabled=2 or a seccomp filter on CI runners
+        if matches!(err.raw_os_error(), Some(libc::ENOSYS | libc::EPERM)) {
+            eprintln!("io_uring_setup is not permitted ({}), skipping", err);
+            return;
+        }
+        panic!("io_uring_setup failed: {}", err);
+    }
+    let fd = ret as RawFd;
+
+    let options = ScanOptions { pid: Some(std::process::id()), ..Default::default() };
+    let result = scan_processes(&options);
+    unsafe {
+        libc::close(fd);
+    }
+
+    let result = result.expect("scan failed");
+    let detection = result
+        .detections
+        .iter()
+        .find(|detection| detection.pid == std::process::id())
+        .expect("the test process was not detected");
+    let ring = detection.rings.iter().find(|ring| ring.fd == fd).expect("the ring fd was not reported");
+    assert_eq!(ring.sq_entries, Some(8));
+}